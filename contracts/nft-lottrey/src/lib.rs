@@ -1,5 +1,15 @@
 #![no_std]
-use soroban_sdk::{contract, contractimpl, contracttype, token, Address, Env, String, Vec};
+use soroban_sdk::{
+    contract, contractimpl, contracttype, symbol_short, token, xdr::ToXdr, Address, BytesN, Env,
+    Map, String, Vec,
+};
+
+#[derive(Clone)]
+#[contracttype]
+pub struct NFTAttribute {
+    pub trait_type: String,
+    pub value: String,
+}
 
 #[derive(Clone)]
 #[contracttype]
@@ -7,6 +17,84 @@ pub struct NFTMetadata {
     pub name: String,
     pub image_url: String,
     pub rarity: u32, // 1=Common, 2=Rare, 3=Epic, 4=Legendary
+    pub description: String,
+    pub attributes: Vec<NFTAttribute>,
+    pub collection_id: Option<String>,
+    pub token_uri: String, // off-chain metadata JSON, e.g. ipfs://...
+}
+
+/// Prize metadata in the common NFT metadata JSON layout used by marketplaces
+#[derive(Clone)]
+#[contracttype]
+pub struct MarketplaceMetadata {
+    pub name: String,
+    pub description: String,
+    pub image: String,
+    pub external_url: String, // web page for the item; prizes don't have one
+    pub token_uri: String,    // where the metadata JSON itself lives
+    pub collection: Option<String>,
+    pub attributes: Vec<NFTAttribute>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum LotteryStatus {
+    Open,      // selling tickets
+    SoldOut,   // every ticket sold, awaiting the draw
+    Drawn,     // winner drawn, prize awaiting claim
+    Claimed,   // winner collected the prize
+    Reclaimed, // claim window expired and the admin took the prize back
+    Cancelled, // cancelled by the admin, buyers can claim refunds
+    Failed,    // mega lottery missed its minimum, pot rolled into the jackpot
+}
+
+/// Every state-changing operation on a lottery
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LotteryAction {
+    Buy,
+    SellOut,
+    AddTickets,
+    Draw,
+    Redraw,
+    Claim,
+    Reclaim,
+    Cancel,
+    Fail,
+}
+
+/// The lottery state machine: the status reached by applying `action`,
+/// or the reason the action is not allowed
+pub fn try_transition(
+    status: LotteryStatus,
+    action: LotteryAction,
+) -> Result<LotteryStatus, &'static str> {
+    use LotteryAction::*;
+    use LotteryStatus::*;
+
+    match (status, action) {
+        (Open, Buy) => Ok(Open),
+        (Open, SellOut) => Ok(SoldOut),
+        (Open | SoldOut, AddTickets) => Ok(Open),
+        (Open | SoldOut, Draw) => Ok(Drawn),
+        (Open | SoldOut, Cancel) => Ok(Cancelled),
+        (Open | SoldOut, Fail) => Ok(Failed),
+        (Drawn, Redraw) => Ok(Drawn),
+        (Drawn, Claim) => Ok(Claimed),
+        (Drawn, Reclaim) => Ok(Reclaimed),
+        (_, Buy | SellOut | AddTickets) => Err("Lottery not active"),
+        (_, Draw | Cancel | Fail) => Err("Lottery already ended"),
+        (Claimed, Claim) => Err("Prize already claimed"),
+        (Claimed | Reclaimed, Redraw | Claim | Reclaim) => Err("Prize already settled"),
+        (_, Redraw | Claim | Reclaim) => Err("Winner not drawn"),
+    }
+}
+
+/// Apply `action` to the lottery, panicking if the transition is invalid
+fn advance(lottery: &mut Lottery, action: LotteryAction) {
+    match try_transition(lottery.status, action) {
+        Ok(status) => lottery.status = status,
+        Err(reason) => panic!("{}", reason),
+    }
 }
 
 #[derive(Clone)]
@@ -16,9 +104,35 @@ pub struct Lottery {
     pub ticket_price: i128,
     pub max_tickets: u32,
     pub tickets_sold: u32,
-    pub is_active: bool,
+    pub status: LotteryStatus,
     pub winner: Option<Address>,
     pub nft_prize: NFTMetadata,
+    pub payment_token: Address,
+    pub fee_bps: Option<u32>, // overrides the global fee when set
+    pub pot: i128,            // ticket proceeds net of platform fees
+    pub is_mega: bool,        // mega series: pot is paid out and rolls over on failure
+    pub min_tickets: u32,     // mega lotteries fail below this many tickets
+    pub jackpot: i128,        // rolled-over jackpot seeded into this lottery
+    pub claim_deadline: u64,  // ledger timestamp after which the prize can be reclaimed
+    pub paused: bool,
+    pub end_time: u64, // ledger timestamp when sales close, 0 for no deadline
+    pub series_id: Option<u64>,
+    pub round: u32, // round number within the series, 0 for standalone lotteries
+}
+
+/// Template for a recurring lottery whose rounds open one after another
+#[derive(Clone)]
+#[contracttype]
+pub struct LotterySeries {
+    pub id: u64,
+    pub ticket_price: i128,
+    pub max_tickets: u32,
+    pub duration: u64, // seconds each round is on sale, 0 for no deadline
+    pub payment_token: Address,
+    pub nft_prize: NFTMetadata,
+    pub current_round: u32,
+    pub current_lottery_id: u64,
+    pub is_active: bool,
 }
 
 #[derive(Clone)]
@@ -27,6 +141,121 @@ pub struct Ticket {
     pub lottery_id: u64,
     pub owner: Address,
     pub ticket_number: u32,
+    pub weight: u32, // relative chance of winning, fixed when the ticket is issued
+}
+
+/// Early-bird price that applies while fewer than `max_sold` tickets are sold
+/// and until `ends_at`; a zero limit is ignored
+#[derive(Clone)]
+#[contracttype]
+pub struct PriceTier {
+    pub max_sold: u32,
+    pub ends_at: u64,
+    pub price: i128,
+}
+
+/// Per-lottery pricing: the first matching tier sets the unit price, and in
+/// every `bundle_size` tickets of a purchase the last `bundle_free` are free
+#[derive(Clone)]
+#[contracttype]
+pub struct PricingConfig {
+    pub tiers: Vec<PriceTier>,
+    pub bundle_size: u32,
+    pub bundle_free: u32,
+}
+
+/// Action that needs M-of-N approver sign-off. Draws and withdrawals are
+/// only guarded on lotteries flagged as requiring approval; approver changes
+/// are proposed under `APPROVERS_PROPOSAL_ID`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum ApprovalAction {
+    Draw,
+    Withdraw(Address),               // send the pot to this recipient
+    SetApprovers(Vec<Address>, u32), // (approvers, threshold)
+    DisableApproval,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Proposal {
+    pub action: ApprovalAction,
+    pub approvals: Vec<Address>,
+    pub expires_at: u64,
+}
+
+/// Sensitive admin change that only takes effect after the timelock delay
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum TimelockAction {
+    SetFee(u32, Address),            // (fee_bps, recipient)
+    SetLotteryFee(u64, Option<u32>), // (lottery_id, override or None to clear)
+    SetReferralBps(u32),
+    SetClaimPeriod(u64),
+    SetPaymentToken(Address),
+    SetAdmin(Address),
+    Upgrade(BytesN<32>), // new wasm hash
+    SetDelay(u64),
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct QueuedAction {
+    pub action: TimelockAction,
+    pub eta: u64, // earliest ledger timestamp it can be executed
+}
+
+/// Lifetime participation counters for an address
+#[derive(Clone, Default)]
+#[contracttype]
+pub struct UserStats {
+    pub tickets_bought: u32,
+    pub lotteries_entered: u32,
+    pub wins: u32,
+}
+
+/// Contract-wide aggregates for operator dashboards
+#[derive(Clone)]
+#[contracttype]
+pub struct ContractStats {
+    pub total_lotteries: u64,
+    pub active_lotteries: u64,
+    pub total_tickets: u64,
+    pub unique_participants: u32,
+    pub volume: Map<Address, i128>, // payment token -> gross ticket sales
+    pub fees: Map<Address, i128>,   // payment token -> protocol fees taken
+}
+
+/// Reward every non-winning participant can claim once a lottery is drawn
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+#[contracttype]
+pub enum Consolation {
+    Nft(NFTMetadata), // Common-rarity consolation NFT
+    Rebate(i128),     // token rebate paid from the pot
+}
+
+#[derive(Clone, Default)]
+#[contracttype]
+pub struct ReferralStats {
+    pub referred_purchases: u32,
+    pub referred_tickets: u32,
+}
+
+/// Token id of a ticket in the ticket NFT interface
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct TicketId {
+    pub lottery_id: u64,
+    pub ticket_number: u32,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct DrawRecord {
+    pub ticket_number: u32,
+    pub winner: Address,
+    pub timestamp: u64,
 }
 
 #[contracttype]
@@ -36,199 +265,2499 @@ pub enum DataKey {
     Lottery(u64),
     Tickets(u64),              // lottery_id -> Vec<Ticket>
     UserTickets(Address, u64), // (user, lottery_id) -> Vec<u32>
-    PaymentToken,
+    PaymentToken,              // default token for lotteries created without one
+    VoucherSigner,             // ed25519 public key signing free-entry vouchers
+    VoucherNonce(u64),         // nonce -> used
+    FeeBps,                    // global platform fee in basis points
+    FeeRecipient,
+    AccumulatedFees(Address),   // payment_token -> unclaimed fees
+    Jackpot(Address),           // payment_token -> progressive jackpot
+    ClaimPeriod,                // seconds a winner has to claim the prize
+    Draws(u64),                 // lottery_id -> Vec<DrawRecord>, oldest first
+    Contribution(Address, u64), // (payer, lottery_id) -> amount paid into the pot
+    Paused,                     // emergency stop for sales and draws
+    SeriesCount,
+    Series(u64),
+    Operator(Address, Address), // (owner, operator) -> may move all of owner's tickets
+    TicketApproval(u64, u32),   // (lottery_id, ticket_number) -> approved spender
+    TicketBalance(Address),     // owner -> tickets held across all lotteries
+    Pricing(u64),               // lottery_id -> PricingConfig
+    ReferralBps,                // share of each referred purchase paid to the referrer
+    ReferralRewards(Address, Address), // (referrer, payment_token) -> unclaimed rewards
+    ReferralStats(Address),     // referrer -> ReferralStats
+    PendingReferral(Address, u64), // (referrer, lottery_id) -> rewards held until the draw
+    LotteryReferrers(u64),      // lottery_id -> Vec<Address> with pending rewards
+    ReferralPaid(Address, u64), // (payer, lottery_id) -> rewards taken from their purchases
+    EntryWeight(Address),       // participant -> weight of tickets issued to them
+    Consolation(u64),           // lottery_id -> Consolation
+    ConsolationClaimed(Address, u64), // (participant, lottery_id) -> claimed
+    ConsolationReserve(u64),    // lottery_id -> rebates set aside from the pot at draw
+    Holders(u64),               // lottery_id -> addresses currently holding tickets
+    UserStats(Address),         // participant -> UserStats
+    ParticipantCount,           // number of addresses that ever held a ticket
+    Participant(u32),           // index -> participant, in order of first entry
+    Leaderboard,                // Vec<(Address, UserStats)> of the top participants
+    Stats,                      // ContractStats
+    Approvers,                  // Vec<Address> allowed to approve actions
+    ApprovalThreshold,          // approvals needed to execute an action
+    ApprovalWindow,             // seconds a proposal stays open
+    RequiresApproval(u64),      // lottery_id -> draws and withdrawals need approval
+    Proposal(u64, BytesN<32>),  // (lottery_id, action hash) -> Proposal
+    TimelockDelay,              // seconds between queueing and executing an action
+    Queued(BytesN<32>),         // action hash -> QueuedAction
 }
 
-#[contract]
-pub struct NFTLotteryContract;
+/// Upper bound for any platform fee (10%)
+pub const MAX_FEE_BPS: u32 = 1_000;
+/// Upper bound for a participant's ticket weight
+pub const MAX_ENTRY_WEIGHT: u32 = 100;
+/// Upper bound for the referral reward (10%)
+pub const MAX_REFERRAL_BPS: u32 = 1_000;
+const BPS_DENOMINATOR: i128 = 10_000;
+/// Claim window used until the admin sets one (7 days)
+pub const DEFAULT_CLAIM_PERIOD: u64 = 7 * 24 * 60 * 60;
+/// Approval window used until the admin sets one (1 day)
+pub const DEFAULT_APPROVAL_WINDOW: u64 = 24 * 60 * 60;
+/// Lottery id that contract-wide approval proposals are filed under
+pub const APPROVERS_PROPOSAL_ID: u64 = 0;
+/// Number of participants kept on the leaderboard
+pub const LEADERBOARD_SIZE: u32 = 20;
+/// Timelock delay used until one is set through the timelock (2 days)
+pub const DEFAULT_TIMELOCK_DELAY: u64 = 2 * 24 * 60 * 60;
+/// Upper bound for the timelock delay (30 days)
+pub const MAX_TIMELOCK_DELAY: u64 = 30 * 24 * 60 * 60;
+/// Upper bound for the prize claim period (1 year)
+pub const MAX_CLAIM_PERIOD: u64 = 365 * 24 * 60 * 60;
 
-#[contractimpl]
-impl NFTLotteryContract {
-    /// Initialize the contract with admin and payment token
-    pub fn initialize(env: Env, admin: Address, payment_token: Address) {
-        if env.storage().instance().has(&DataKey::Admin) {
-            panic!("Already initialized");
-        }
+/// Require auth from the stored admin, which may be an account or a
+/// custom account contract such as a multisig
+fn require_admin(env: &Env) {
+    let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+    admin.require_auth();
+}
 
-        env.storage().instance().set(&DataKey::Admin, &admin);
-        env.storage()
-            .instance()
-            .set(&DataKey::PaymentToken, &payment_token);
-        env.storage().instance().set(&DataKey::LotteryCount, &0u64);
+/// Sales and draws are blocked while the contract or the lottery is paused
+fn require_not_paused(env: &Env, lottery: &Lottery) {
+    if env
+        .storage()
+        .instance()
+        .get(&DataKey::Paused)
+        .unwrap_or(false)
+    {
+        panic!("Contract paused");
     }
 
-    /// Create a new lottery
-    pub fn create_lottery(
-        env: Env,
-        admin: Address,
-        ticket_price: i128,
-        max_tickets: u32,
-        nft_name: String,
-        nft_image: String,
-        nft_rarity: u32,
-    ) -> u64 {
-        admin.require_auth();
+    if lottery.paused {
+        panic!("Lottery paused");
+    }
+}
 
-        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
-        if admin != stored_admin {
-            panic!("Unauthorized");
-        }
+/// Metadata with only the name, image and rarity filled in
+fn basic_metadata(env: &Env, name: String, image_url: String, rarity: u32) -> NFTMetadata {
+    NFTMetadata {
+        name,
+        image_url,
+        rarity,
+        description: String::from_str(env, ""),
+        attributes: Vec::new(env),
+        collection_id: None,
+        token_uri: String::from_str(env, ""),
+    }
+}
 
-        let lottery_count: u64 = env
-            .storage()
-            .instance()
-            .get(&DataKey::LotteryCount)
-            .unwrap_or(0);
-        let lottery_id = lottery_count + 1;
+fn rarity_name(env: &Env, rarity: u32) -> String {
+    match rarity {
+        1 => String::from_str(env, "Common"),
+        2 => String::from_str(env, "Rare"),
+        3 => String::from_str(env, "Epic"),
+        4 => String::from_str(env, "Legendary"),
+        _ => String::from_str(env, "Unknown"),
+    }
+}
 
-        let nft_metadata = NFTMetadata {
-            name: nft_name,
-            image_url: nft_image,
-            rarity: nft_rarity,
-        };
+/// Ticket sales close at the lottery's end time, if it has one
+fn require_sales_open(env: &Env, lottery: &Lottery) {
+    if lottery.end_time != 0 && env.ledger().timestamp() > lottery.end_time {
+        panic!("Sales closed");
+    }
+}
 
-        let lottery = Lottery {
-            id: lottery_id,
-            ticket_price,
-            max_tickets,
-            tickets_sold: 0,
-            is_active: true,
-            winner: None,
-            nft_prize: nft_metadata,
-        };
+/// Allocate the next lottery id and an empty ticket list. The caller stores
+/// the returned lottery once any remaining fields are filled in.
+fn new_lottery(
+    env: &Env,
+    ticket_price: i128,
+    max_tickets: u32,
+    nft_prize: NFTMetadata,
+    payment_token: Address,
+) -> Lottery {
+    let lottery_count: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKey::LotteryCount)
+        .unwrap_or(0);
+    let lottery_id = lottery_count + 1;
 
-        env.storage()
-            .persistent()
-            .set(&DataKey::Lottery(lottery_id), &lottery);
-        env.storage()
-            .instance()
-            .set(&DataKey::LotteryCount, &lottery_id);
+    env.storage()
+        .instance()
+        .set(&DataKey::LotteryCount, &lottery_id);
 
-        // Initialize empty ticket list
-        let empty_tickets: Vec<Ticket> = Vec::new(&env);
-        env.storage()
-            .persistent()
-            .set(&DataKey::Tickets(lottery_id), &empty_tickets);
+    update_stats(env, |stats| {
+        stats.total_lotteries += 1;
+        stats.active_lotteries += 1;
+    });
+
+    // Initialize empty ticket list
+    let empty_tickets: Vec<Ticket> = Vec::new(env);
+    env.storage()
+        .persistent()
+        .set(&DataKey::Tickets(lottery_id), &empty_tickets);
 
-        lottery_id
+    Lottery {
+        id: lottery_id,
+        ticket_price,
+        max_tickets,
+        tickets_sold: 0,
+        status: LotteryStatus::Open,
+        winner: None,
+        nft_prize,
+        payment_token,
+        fee_bps: None,
+        pot: 0,
+        is_mega: false,
+        min_tickets: 0,
+        jackpot: 0,
+        claim_deadline: 0,
+        paused: false,
+        end_time: 0,
+        series_id: None,
+        round: 0,
     }
+}
 
-    /// Buy lottery tickets
-    pub fn buy_ticket(env: Env, buyer: Address, lottery_id: u64, num_tickets: u32) {
-        buyer.require_auth();
+/// Open the next round of a series from its template and store both
+fn open_next_round(env: &Env, series: &mut LotterySeries) {
+    let mut lottery = new_lottery(
+        env,
+        series.ticket_price,
+        series.max_tickets,
+        series.nft_prize.clone(),
+        series.payment_token.clone(),
+    );
 
-        let mut lottery: Lottery = env
+    series.current_round += 1;
+    series.current_lottery_id = lottery.id;
+
+    lottery.series_id = Some(series.id);
+    lottery.round = series.current_round;
+    if series.duration > 0 {
+        lottery.end_time = env.ledger().timestamp() + series.duration;
+    }
+
+    env.storage()
+        .persistent()
+        .set(&DataKey::Lottery(lottery.id), &lottery);
+    env.storage()
+        .persistent()
+        .set(&DataKey::Series(series.id), series);
+}
+
+/// Open the next round when `lottery` was the current round of an active
+/// series, whether it was drawn, cancelled or failed
+fn roll_series(env: &Env, lottery: &Lottery) {
+    if let Some(series_id) = lottery.series_id {
+        let mut series: LotterySeries = env
             .storage()
             .persistent()
-            .get(&DataKey::Lottery(lottery_id))
+            .get(&DataKey::Series(series_id))
             .unwrap();
+        if series.is_active && series.current_lottery_id == lottery.id {
+            open_next_round(env, &mut series);
+        }
+    }
+}
 
-        if !lottery.is_active {
-            panic!("Lottery not active");
+fn cancel(env: &Env, lottery: &mut Lottery) {
+    advance(lottery, LotteryAction::Cancel);
+    update_stats(env, |stats| stats.active_lotteries -= 1);
+
+    // A seeded jackpot goes back for the next mega lottery
+    if lottery.jackpot > 0 {
+        add_to_jackpot(env, &lottery.payment_token, lottery.jackpot);
+        lottery.jackpot = 0;
+    }
+
+    env.storage()
+        .persistent()
+        .set(&DataKey::Lottery(lottery.id), lottery);
+    roll_series(env, lottery);
+}
+
+/// Platform fee on `amount`, rounded down in favour of the pot
+fn compute_fee(env: &Env, lottery: &Lottery, amount: i128) -> i128 {
+    let fee_bps: u32 = lottery
+        .fee_bps
+        .unwrap_or_else(|| env.storage().instance().get(&DataKey::FeeBps).unwrap_or(0));
+    amount * (fee_bps as i128) / BPS_DENOMINATOR
+}
+
+fn add_to_jackpot(env: &Env, token: &Address, amount: i128) {
+    let key = DataKey::Jackpot(token.clone());
+    let jackpot: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+    env.storage().persistent().set(&key, &(jackpot + amount));
+}
+
+/// Pick a random ticket weighted by ticket weight, skipping previously
+/// drawn ticket numbers
+fn pick_ticket(env: &Env, lottery_id: u64, excluded: &Vec<u32>) -> Ticket {
+    let all_tickets: Vec<Ticket> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Tickets(lottery_id))
+        .unwrap();
+
+    let mut eligible: Vec<Ticket> = Vec::new(env);
+    let mut total_weight: u64 = 0;
+    for ticket in all_tickets.iter() {
+        if !excluded.contains(ticket.ticket_number) {
+            total_weight += ticket.weight as u64;
+            eligible.push_back(ticket);
         }
+    }
 
-        if lottery.tickets_sold + num_tickets > lottery.max_tickets {
-            panic!("Not enough tickets available");
+    if eligible.is_empty() {
+        panic!("No eligible tickets");
+    }
+
+    let target = env.prng().gen_range::<u64>(0..total_weight);
+    weighted_pick(&eligible, target)
+}
+
+/// The ticket whose cumulative weight range contains `target`
+fn weighted_pick(tickets: &Vec<Ticket>, target: u64) -> Ticket {
+    let mut cumulative: u64 = 0;
+    for ticket in tickets.iter() {
+        cumulative += ticket.weight as u64;
+        if target < cumulative {
+            return ticket;
         }
+    }
+    panic!("Target out of range");
+}
 
-        // Transfer payment
-        let payment_token: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::PaymentToken)
-            .unwrap();
-        let token_client = token::Client::new(&env, &payment_token);
-        let total_cost = lottery.ticket_price * (num_tickets as i128);
+/// Record `ticket` as the winner of `lottery` and open a fresh claim window
+fn record_draw(env: &Env, lottery: &mut Lottery, ticket: &Ticket) {
+    let mut draws: Vec<DrawRecord> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Draws(lottery.id))
+        .unwrap_or(Vec::new(env));
+    draws.push_back(DrawRecord {
+        ticket_number: ticket.ticket_number,
+        winner: ticket.owner.clone(),
+        timestamp: env.ledger().timestamp(),
+    });
+    env.storage()
+        .persistent()
+        .set(&DataKey::Draws(lottery.id), &draws);
 
-        token_client.transfer(&buyer, &env.current_contract_address(), &total_cost);
+    let claim_period: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKey::ClaimPeriod)
+        .unwrap_or(DEFAULT_CLAIM_PERIOD);
 
-        // Create tickets
-        let mut all_tickets: Vec<Ticket> = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Tickets(lottery_id))
-            .unwrap();
+    if let Some(previous) = &lottery.winner {
+        update_user_stats(env, previous, |stats| stats.wins -= 1);
+    }
+    update_user_stats(env, &ticket.owner, |stats| stats.wins += 1);
 
-        let mut user_ticket_numbers: Vec<u32> = env
-            .storage()
-            .persistent()
-            .get(&DataKey::UserTickets(buyer.clone(), lottery_id))
-            .unwrap_or(Vec::new(&env));
+    lottery.winner = Some(ticket.owner.clone());
+    lottery.claim_deadline = env
+        .ledger()
+        .timestamp()
+        .checked_add(claim_period)
+        .expect("Claim period too long");
+}
 
-        for i in 0..num_tickets {
-            let ticket_number = lottery.tickets_sold + i + 1;
-            let ticket = Ticket {
-                lottery_id,
-                owner: buyer.clone(),
-                ticket_number,
-            };
-            all_tickets.push_back(ticket);
-            user_ticket_numbers.push_back(ticket_number);
+/// Append `num_tickets` tickets owned by `owner` to the lottery
+fn issue_tickets(env: &Env, lottery: &mut Lottery, owner: &Address, num_tickets: u32) {
+    let mut all_tickets: Vec<Ticket> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Tickets(lottery.id))
+        .unwrap();
+
+    let mut user_ticket_numbers: Vec<u32> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::UserTickets(owner.clone(), lottery.id))
+        .unwrap_or(Vec::new(env));
+
+    let weight: u32 = env
+        .storage()
+        .persistent()
+        .get(&DataKey::EntryWeight(owner.clone()))
+        .unwrap_or(1);
+
+    // Holders and participant stats only change once a ticket is issued
+    if num_tickets == 0 {
+        return;
+    }
+
+    let first_entry = user_ticket_numbers.is_empty();
+    if first_entry {
+        add_holders(env, lottery.id, 1);
+    }
+    update_user_stats(env, owner, |stats| {
+        stats.tickets_bought += num_tickets;
+        if first_entry {
+            stats.lotteries_entered += 1;
         }
+    });
+
+    for i in 0..num_tickets {
+        let ticket_number = lottery.tickets_sold + i + 1;
+        let ticket = Ticket {
+            lottery_id: lottery.id,
+            owner: owner.clone(),
+            ticket_number,
+            weight,
+        };
+        all_tickets.push_back(ticket);
+        user_ticket_numbers.push_back(ticket_number);
+    }
+
+    lottery.tickets_sold += num_tickets;
+    add_ticket_balance(env, owner, num_tickets as i64);
+    update_stats(env, |stats| stats.total_tickets += num_tickets as u64);
+    if lottery.tickets_sold == lottery.max_tickets {
+        advance(lottery, LotteryAction::SellOut);
+    }
+
+    // Save updates
+    env.storage()
+        .persistent()
+        .set(&DataKey::Lottery(lottery.id), lottery);
+    env.storage()
+        .persistent()
+        .set(&DataKey::Tickets(lottery.id), &all_tickets);
+    env.storage().persistent().set(
+        &DataKey::UserTickets(owner.clone(), lottery.id),
+        &user_ticket_numbers,
+    );
 
-        lottery.tickets_sold += num_tickets;
+    for ticket_number in lottery.tickets_sold - num_tickets + 1..=lottery.tickets_sold {
+        env.events().publish(
+            (symbol_short!("mint"), owner.clone()),
+            (lottery.id, ticket_number),
+        );
+    }
+}
 
-        // Save updates
+fn update_user_stats(env: &Env, user: &Address, update: impl FnOnce(&mut UserStats)) {
+    let key = DataKey::UserStats(user.clone());
+    let existing: Option<UserStats> = env.storage().persistent().get(&key);
+    if existing.is_none() {
+        let count: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ParticipantCount)
+            .unwrap_or(0);
         env.storage()
             .persistent()
-            .set(&DataKey::Lottery(lottery_id), &lottery);
+            .set(&DataKey::Participant(count), user);
         env.storage()
-            .persistent()
-            .set(&DataKey::Tickets(lottery_id), &all_tickets);
-        env.storage().persistent().set(
-            &DataKey::UserTickets(buyer, lottery_id),
-            &user_ticket_numbers,
-        );
+            .instance()
+            .set(&DataKey::ParticipantCount, &(count + 1));
     }
 
-    /// Draw winner (only admin)
-    pub fn draw_winner(env: Env, admin: Address, lottery_id: u64) -> Address {
-        admin.require_auth();
+    let mut stats = existing.unwrap_or_default();
+    update(&mut stats);
+    env.storage().persistent().set(&key, &stats);
+    update_leaderboard(env, user, &stats);
+}
 
-        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
-        if admin != stored_admin {
-            panic!("Unauthorized");
-        }
+/// Re-rank `user` on the bounded leaderboard after their stats changed
+fn update_leaderboard(env: &Env, user: &Address, stats: &UserStats) {
+    let mut board: Vec<(Address, UserStats)> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Leaderboard)
+        .unwrap_or(Vec::new(env));
 
-        let mut lottery: Lottery = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Lottery(lottery_id))
-            .unwrap();
+    if let Some(index) = board.iter().position(|(ranked, _)| ranked == *user) {
+        board.remove(index as u32);
+    }
 
-        if !lottery.is_active {
-            panic!("Lottery already ended");
+    let mut position = board.len();
+    for (i, (_, ranked)) in board.iter().enumerate() {
+        if (stats.tickets_bought, stats.wins) > (ranked.tickets_bought, ranked.wins) {
+            position = i as u32;
+            break;
         }
+    }
+    if position >= LEADERBOARD_SIZE {
+        return;
+    }
 
-        if lottery.tickets_sold == 0 {
-            panic!("No tickets sold");
-        }
+    board.insert(position, (user.clone(), stats.clone()));
+    if board.len() > LEADERBOARD_SIZE {
+        board.pop_back();
+    }
+    env.storage()
+        .persistent()
+        .set(&DataKey::Leaderboard, &board);
+}
 
-        let all_tickets: Vec<Ticket> = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Tickets(lottery_id))
-            .unwrap();
+fn action_hash(env: &Env, action: &ApprovalAction) -> BytesN<32> {
+    env.crypto().sha256(&action.clone().to_xdr(env)).to_bytes()
+}
 
-        // Simple random selection using ledger sequence
-        let random_index = (env.ledger().sequence() as u32) % lottery.tickets_sold;
-        let winning_ticket = all_tickets.get(random_index).unwrap();
+fn require_token(env: &Env, token: &Address) {
+    if !matches!(token::Client::new(env, token).try_decimals(), Ok(Ok(_))) {
+        panic!("Invalid payment token");
+    }
+}
 
-        lottery.winner = Some(winning_ticket.owner.clone());
-        lottery.is_active = false;
+fn require_approver(env: &Env, approver: &Address) {
+    approver.require_auth();
 
-        env.storage()
-            .persistent()
-            .set(&DataKey::Lottery(lottery_id), &lottery);
+    let approvers: Vec<Address> = env
+        .storage()
+        .instance()
+        .get(&DataKey::Approvers)
+        .unwrap_or(Vec::new(env));
+    if !approvers.contains(approver) {
+        panic!("Not an approver");
+    }
+}
 
-        winning_ticket.owner
+fn requires_approval(env: &Env, lottery_id: u64) -> bool {
+    env.storage()
+        .persistent()
+        .get(&DataKey::RequiresApproval(lottery_id))
+        .unwrap_or(false)
+}
+
+/// Execute-time check for lotteries flagged as requiring approval
+fn consume_approval(env: &Env, lottery_id: u64, action: &ApprovalAction) {
+    if requires_approval(env, lottery_id) {
+        require_approval(env, lottery_id, action);
     }
+}
 
-    /// Get lottery details
-    pub fn get_lottery(env: Env, lottery_id: u64) -> Lottery {
+/// The matching proposal must be live and signed by enough current
+/// approvers. The proposal is consumed so it can't be replayed.
+fn require_approval(env: &Env, lottery_id: u64, action: &ApprovalAction) {
+    let hash = action_hash(env, action);
+    let key = DataKey::Proposal(lottery_id, hash.clone());
+    let proposal: Proposal = env
+        .storage()
+        .persistent()
+        .get(&key)
+        .expect("Approval required");
+
+    if env.ledger().timestamp() > proposal.expires_at {
+        panic!("Proposal expired");
+    }
+
+    let approvers: Vec<Address> = env
+        .storage()
+        .instance()
+        .get(&DataKey::Approvers)
+        .unwrap_or(Vec::new(env));
+    let threshold: u32 = env
+        .storage()
+        .instance()
+        .get(&DataKey::ApprovalThreshold)
+        .unwrap();
+    let approvals = proposal
+        .approvals
+        .iter()
+        .filter(|approver| approvers.contains(approver))
+        .count() as u32;
+    if approvals < threshold {
+        panic!("Not enough approvals");
+    }
+
+    env.storage().persistent().remove(&key);
+    env.events()
+        .publish((symbol_short!("executed"), lottery_id), hash);
+}
+
+fn add_holders(env: &Env, lottery_id: u64, delta: i32) {
+    let key = DataKey::Holders(lottery_id);
+    let holders: u32 = env.storage().persistent().get(&key).unwrap_or(0);
+    env.storage()
+        .persistent()
+        .set(&key, &((holders as i32 + delta) as u32));
+}
+
+/// Move the rebates owed to every non-winning holder out of the pot, so a
+/// winner's payout or a pot withdrawal can't spend them
+fn reserve_consolation(env: &Env, lottery: &mut Lottery) {
+    let consolation: Option<Consolation> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Consolation(lottery.id));
+    if let Some(Consolation::Rebate(amount)) = consolation {
+        let holders: u32 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Holders(lottery.id))
+            .unwrap_or(0);
+        let reserve = (amount * (holders as i128 - 1)).min(lottery.pot);
+        lottery.pot -= reserve;
         env.storage()
             .persistent()
-            .get(&DataKey::Lottery(lottery_id))
+            .set(&DataKey::ConsolationReserve(lottery.id), &reserve);
+    }
+}
+
+fn load_stats(env: &Env) -> ContractStats {
+    env.storage()
+        .instance()
+        .get(&DataKey::Stats)
+        .unwrap_or(ContractStats {
+            total_lotteries: 0,
+            active_lotteries: 0,
+            total_tickets: 0,
+            unique_participants: 0,
+            volume: Map::new(env),
+            fees: Map::new(env),
+        })
+}
+
+fn update_stats(env: &Env, update: impl FnOnce(&mut ContractStats)) {
+    let mut stats = load_stats(env);
+    update(&mut stats);
+    env.storage().instance().set(&DataKey::Stats, &stats);
+}
+
+fn add_ticket_balance(env: &Env, owner: &Address, delta: i64) {
+    let key = DataKey::TicketBalance(owner.clone());
+    let balance: u32 = env.storage().persistent().get(&key).unwrap_or(0);
+    env.storage()
+        .persistent()
+        .set(&key, &((balance as i64 + delta) as u32));
+}
+
+/// Panic unless `spender` may move the ticket for `owner`
+fn require_spender(
+    env: &Env,
+    spender: &Address,
+    owner: &Address,
+    lottery_id: u64,
+    ticket_number: u32,
+) {
+    let operator = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Operator(owner.clone(), spender.clone()))
+        .unwrap_or(false);
+    let approved: Option<Address> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::TicketApproval(lottery_id, ticket_number));
+
+    if !operator && approved.as_ref() != Some(spender) {
+        panic!("Not approved");
+    }
+}
+
+/// Price of the next `num_tickets` tickets after tiers and bundle discounts
+fn quote(env: &Env, lottery: &Lottery, num_tickets: u32) -> i128 {
+    let pricing: Option<PricingConfig> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Pricing(lottery.id));
+    let pricing = match pricing {
+        Some(pricing) => pricing,
+        None => return lottery.ticket_price * (num_tickets as i128),
+    };
+
+    let now = env.ledger().timestamp();
+    let mut total: i128 = 0;
+    for i in 0..num_tickets {
+        if pricing.bundle_size > 0
+            && i % pricing.bundle_size >= pricing.bundle_size - pricing.bundle_free
+        {
+            continue;
+        }
+
+        let sold = lottery.tickets_sold + i;
+        let unit_price = pricing
+            .tiers
+            .iter()
+            .find(|tier| {
+                (tier.max_sold == 0 || sold < tier.max_sold)
+                    && (tier.ends_at == 0 || now <= tier.ends_at)
+            })
+            .map(|tier| tier.price)
+            .unwrap_or(lottery.ticket_price);
+        total += unit_price;
+    }
+    total
+}
+
+/// Load and validate a ticket sale, returning the lottery and its total cost
+fn prepare_purchase(env: &Env, lottery_id: u64, num_tickets: u32) -> (Lottery, i128) {
+    let mut lottery: Lottery = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Lottery(lottery_id))
+        .unwrap();
+
+    require_not_paused(env, &lottery);
+    advance(&mut lottery, LotteryAction::Buy);
+    require_sales_open(env, &lottery);
+
+    if lottery.ticket_price == 0 {
+        panic!("Free-entry lottery requires a voucher");
+    }
+
+    if num_tickets == 0 {
+        panic!("Invalid ticket count");
+    }
+
+    if lottery.tickets_sold + num_tickets > lottery.max_tickets {
+        panic!("Not enough tickets available");
+    }
+
+    let total_cost = quote(env, &lottery, num_tickets);
+    (lottery, total_cost)
+}
+
+/// Book a paid sale: take the platform fee and any referral reward, credit
+/// the pot and the payer's refundable contribution, and issue the tickets
+/// to `recipient`
+fn record_purchase(
+    env: &Env,
+    lottery: &mut Lottery,
+    payer: &Address,
+    recipient: &Address,
+    num_tickets: u32,
+    total_cost: i128,
+    referrer: Option<&Address>,
+) {
+    let fee = compute_fee(env, lottery, total_cost);
+    if fee > 0 {
+        let fees_key = DataKey::AccumulatedFees(lottery.payment_token.clone());
+        let accumulated: i128 = env.storage().persistent().get(&fees_key).unwrap_or(0);
+        env.storage()
+            .persistent()
+            .set(&fees_key, &(accumulated + fee));
+    }
+
+    let reward = match referrer {
+        Some(referrer) => reward_referrer(env, lottery, payer, referrer, num_tickets, total_cost),
+        None => 0,
+    };
+
+    update_stats(env, |stats| {
+        let token = &lottery.payment_token;
+        let volume = stats.volume.get(token.clone()).unwrap_or(0);
+        stats.volume.set(token.clone(), volume + total_cost);
+        let fees = stats.fees.get(token.clone()).unwrap_or(0);
+        stats.fees.set(token.clone(), fees + fee);
+    });
+
+    let net = total_cost - fee - reward;
+    lottery.pot += net;
+
+    let contribution_key = DataKey::Contribution(payer.clone(), lottery.id);
+    let contribution: i128 = env
+        .storage()
+        .persistent()
+        .get(&contribution_key)
+        .unwrap_or(0);
+    env.storage()
+        .persistent()
+        .set(&contribution_key, &(contribution + net));
+
+    issue_tickets(env, lottery, recipient, num_tickets);
+}
+
+/// Credit the referrer's share of a purchase and update their stats
+fn reward_referrer(
+    env: &Env,
+    lottery: &Lottery,
+    payer: &Address,
+    referrer: &Address,
+    num_tickets: u32,
+    total_cost: i128,
+) -> i128 {
+    let referral_bps: u32 = env
+        .storage()
+        .instance()
+        .get(&DataKey::ReferralBps)
+        .unwrap_or(0);
+    let reward = total_cost * (referral_bps as i128) / BPS_DENOMINATOR;
+
+    // Held per lottery until the draw, so a cancellation can refund it
+    if reward > 0 {
+        let pending_key = DataKey::PendingReferral(referrer.clone(), lottery.id);
+        let pending: i128 = env.storage().persistent().get(&pending_key).unwrap_or(0);
+        if pending == 0 {
+            let mut referrers: Vec<Address> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::LotteryReferrers(lottery.id))
+                .unwrap_or(Vec::new(env));
+            referrers.push_back(referrer.clone());
+            env.storage()
+                .persistent()
+                .set(&DataKey::LotteryReferrers(lottery.id), &referrers);
+        }
+        env.storage()
+            .persistent()
+            .set(&pending_key, &(pending + reward));
+
+        let paid_key = DataKey::ReferralPaid(payer.clone(), lottery.id);
+        let paid: i128 = env.storage().persistent().get(&paid_key).unwrap_or(0);
+        env.storage().persistent().set(&paid_key, &(paid + reward));
+    }
+
+    let stats_key = DataKey::ReferralStats(referrer.clone());
+    let mut stats: ReferralStats = env
+        .storage()
+        .persistent()
+        .get(&stats_key)
+        .unwrap_or_default();
+    stats.referred_purchases += 1;
+    stats.referred_tickets += num_tickets;
+    env.storage().persistent().set(&stats_key, &stats);
+
+    reward
+}
+
+/// Make a lottery's pending referral rewards claimable once it is drawn or
+/// has failed; cancelled lotteries refund them to the buyers instead
+fn settle_referrals(env: &Env, lottery: &Lottery) {
+    let referrers: Vec<Address> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::LotteryReferrers(lottery.id))
+        .unwrap_or(Vec::new(env));
+
+    for referrer in referrers.iter() {
+        let pending_key = DataKey::PendingReferral(referrer.clone(), lottery.id);
+        let pending: i128 = env.storage().persistent().get(&pending_key).unwrap();
+        env.storage().persistent().remove(&pending_key);
+
+        let rewards_key = DataKey::ReferralRewards(referrer, lottery.payment_token.clone());
+        let rewards: i128 = env.storage().persistent().get(&rewards_key).unwrap_or(0);
+        env.storage()
+            .persistent()
+            .set(&rewards_key, &(rewards + pending));
+    }
+    env.storage()
+        .persistent()
+        .remove(&DataKey::LotteryReferrers(lottery.id));
+}
+
+/// Take payment from `payer` and issue tickets to `recipient`
+fn purchase(
+    env: &Env,
+    payer: &Address,
+    recipient: &Address,
+    lottery_id: u64,
+    num_tickets: u32,
+    referrer: Option<&Address>,
+) {
+    let (mut lottery, total_cost) = prepare_purchase(env, lottery_id, num_tickets);
+
+    // Transfer payment in the lottery's own token
+    let token_client = token::Client::new(env, &lottery.payment_token);
+    token_client.transfer(payer, &env.current_contract_address(), &total_cost);
+
+    record_purchase(
+        env,
+        &mut lottery,
+        payer,
+        recipient,
+        num_tickets,
+        total_cost,
+        referrer,
+    );
+}
+
+/// Move a ticket between owners, keeping both ticket indices in sync
+fn move_ticket(env: &Env, from: &Address, to: &Address, lottery_id: u64, ticket_number: u32) {
+    let lottery: Lottery = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Lottery(lottery_id))
+        .unwrap();
+
+    if lottery.status != LotteryStatus::Open && lottery.status != LotteryStatus::SoldOut {
+        panic!("Lottery not active");
+    }
+
+    if ticket_number == 0 || ticket_number > lottery.tickets_sold {
+        panic!("Ticket not found");
+    }
+
+    let mut all_tickets: Vec<Ticket> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Tickets(lottery_id))
+        .unwrap();
+    let mut ticket = all_tickets.get(ticket_number - 1).unwrap();
+    if ticket.owner != *from {
+        panic!("Not ticket owner");
+    }
+    if from == to {
+        panic!("Cannot transfer to self");
+    }
+
+    ticket.owner = to.clone();
+    all_tickets.set(ticket_number - 1, ticket);
+
+    // A per-ticket approval does not survive a change of owner
+    env.storage()
+        .persistent()
+        .remove(&DataKey::TicketApproval(lottery_id, ticket_number));
+    add_ticket_balance(env, from, -1);
+    add_ticket_balance(env, to, 1);
+
+    let mut from_tickets: Vec<u32> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::UserTickets(from.clone(), lottery_id))
+        .unwrap();
+    let index = from_tickets.first_index_of(ticket_number).unwrap();
+    from_tickets.remove(index);
+    if from_tickets.is_empty() {
+        add_holders(env, lottery_id, -1);
+    }
+
+    let mut to_tickets: Vec<u32> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::UserTickets(to.clone(), lottery_id))
+        .unwrap_or(Vec::new(env));
+    if to_tickets.is_empty() {
+        add_holders(env, lottery_id, 1);
+    }
+    to_tickets.push_back(ticket_number);
+
+    env.storage()
+        .persistent()
+        .set(&DataKey::Tickets(lottery_id), &all_tickets);
+    env.storage().persistent().set(
+        &DataKey::UserTickets(from.clone(), lottery_id),
+        &from_tickets,
+    );
+    env.storage()
+        .persistent()
+        .set(&DataKey::UserTickets(to.clone(), lottery_id), &to_tickets);
+
+    env.events().publish(
+        (symbol_short!("transfer"), from.clone(), to.clone()),
+        (lottery_id, ticket_number),
+    );
+}
+
+#[contract]
+pub struct NFTLotteryContract;
+
+#[contractimpl]
+impl NFTLotteryContract {
+    /// Initialize the contract with admin and payment token. The first
+    /// caller wins, so deployment and initialization must happen in one
+    /// transaction, e.g. through a factory contract.
+    pub fn initialize(env: Env, admin: Address, payment_token: Address) {
+        if env.storage().instance().has(&DataKey::Admin) {
+            panic!("Already initialized");
+        }
+
+        // The admin must sign, so nobody can be made admin without consent
+        admin.require_auth();
+
+        require_token(&env, &payment_token);
+
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage()
+            .instance()
+            .set(&DataKey::PaymentToken, &payment_token);
+        env.storage().instance().set(&DataKey::LotteryCount, &0u64);
+    }
+
+    /// Create a new lottery, priced in `payment_token` or the default token
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_lottery(
+        env: Env,
+        ticket_price: i128,
+        max_tickets: u32,
+        nft_name: String,
+        nft_image: String,
+        nft_rarity: u32,
+        payment_token: Option<Address>,
+    ) -> u64 {
+        let nft_prize = basic_metadata(&env, nft_name, nft_image, nft_rarity);
+        Self::create_lottery_with_metadata(env, ticket_price, max_tickets, nft_prize, payment_token)
+    }
+
+    /// Create a new lottery with full prize metadata (description, traits,
+    /// collection and token URI)
+    pub fn create_lottery_with_metadata(
+        env: Env,
+        ticket_price: i128,
+        max_tickets: u32,
+        nft_prize: NFTMetadata,
+        payment_token: Option<Address>,
+    ) -> u64 {
+        require_admin(&env);
+
+        // Fall back to the contract-wide token when none is given
+        if let Some(token) = &payment_token {
+            require_token(&env, token);
+        }
+        let payment_token = payment_token.unwrap_or_else(|| {
+            env.storage()
+                .instance()
+                .get(&DataKey::PaymentToken)
+                .unwrap()
+        });
+
+        let lottery = new_lottery(&env, ticket_price, max_tickets, nft_prize, payment_token);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery.id), &lottery);
+
+        lottery.id
+    }
+
+    /// Create a recurring lottery series and open its first round (only admin).
+    /// Each round sells for `duration` seconds (0 for no deadline) and the
+    /// next round opens automatically when the current one is drawn.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_series(
+        env: Env,
+        ticket_price: i128,
+        max_tickets: u32,
+        duration: u64,
+        nft_name: String,
+        nft_image: String,
+        nft_rarity: u32,
+        payment_token: Option<Address>,
+    ) -> u64 {
+        require_admin(&env);
+
+        if let Some(token) = &payment_token {
+            require_token(&env, token);
+        }
+        let payment_token = payment_token.unwrap_or_else(|| {
+            env.storage()
+                .instance()
+                .get(&DataKey::PaymentToken)
+                .unwrap()
+        });
+
+        let series_count: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::SeriesCount)
+            .unwrap_or(0);
+        let series_id = series_count + 1;
+
+        let mut series = LotterySeries {
+            id: series_id,
+            ticket_price,
+            max_tickets,
+            duration,
+            payment_token,
+            nft_prize: basic_metadata(&env, nft_name, nft_image, nft_rarity),
+            current_round: 0,
+            current_lottery_id: 0,
+            is_active: true,
+        };
+        open_next_round(&env, &mut series);
+
+        env.storage()
+            .instance()
+            .set(&DataKey::SeriesCount, &series_id);
+
+        series_id
+    }
+
+    /// Stop a series from opening further rounds (only admin)
+    pub fn end_series(env: Env, series_id: u64) {
+        require_admin(&env);
+
+        let mut series: LotterySeries = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Series(series_id))
+            .unwrap();
+        series.is_active = false;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Series(series_id), &series);
+    }
+
+    /// Move a series on to its next round when the current one has sold no
+    /// tickets: the round is cancelled and the next one opened (only admin).
+    /// Rounds that end any other way roll the series on by themselves.
+    pub fn advance_series(env: Env, series_id: u64) -> u64 {
+        require_admin(&env);
+
+        let series: LotterySeries = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Series(series_id))
+            .unwrap();
+        if !series.is_active {
+            panic!("Series ended");
+        }
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(series.current_lottery_id))
+            .unwrap();
+        if lottery.tickets_sold > 0 {
+            panic!("Round in progress");
+        }
+        cancel(&env, &mut lottery);
+
+        Self::get_series(env, series_id).current_lottery_id
+    }
+
+    /// Buy lottery tickets
+    pub fn buy_ticket(env: Env, buyer: Address, lottery_id: u64, num_tickets: u32) {
+        buyer.require_auth();
+
+        purchase(&env, &buyer, &buyer, lottery_id, num_tickets, None);
+    }
+
+    /// Buy lottery tickets on a referral; the referrer earns a share of the
+    /// purchase, claimable with `claim_referral_rewards`
+    pub fn buy_ticket_with_referrer(
+        env: Env,
+        buyer: Address,
+        lottery_id: u64,
+        num_tickets: u32,
+        referrer: Address,
+    ) {
+        buyer.require_auth();
+
+        if referrer == buyer {
+            panic!("Self-referral not allowed");
+        }
+
+        purchase(
+            &env,
+            &buyer,
+            &buyer,
+            lottery_id,
+            num_tickets,
+            Some(&referrer),
+        );
+    }
+
+    /// Buy lottery tickets paid by `payer` and owned by `recipient`, for gifts
+    /// and custodial integrations. Refunds go back to the payer.
+    pub fn buy_ticket_for(
+        env: Env,
+        payer: Address,
+        recipient: Address,
+        lottery_id: u64,
+        num_tickets: u32,
+    ) {
+        payer.require_auth();
+
+        purchase(&env, &payer, &recipient, lottery_id, num_tickets, None);
+    }
+
+    /// Buy tickets in several lotteries with a single payment. Every
+    /// `(lottery_id, num_tickets)` entry is validated before anything is
+    /// charged, and all lotteries must share one payment token.
+    pub fn buy_tickets_batch(env: Env, buyer: Address, purchases: Vec<(u64, u32)>) {
+        buyer.require_auth();
+
+        if purchases.is_empty() {
+            panic!("Empty batch");
+        }
+
+        let mut lotteries: Vec<(Lottery, u32, i128)> = Vec::new(&env);
+        let mut total_cost: i128 = 0;
+        for (lottery_id, num_tickets) in purchases.iter() {
+            if lotteries
+                .iter()
+                .any(|(lottery, _, _)| lottery.id == lottery_id)
+            {
+                panic!("Duplicate lottery in batch");
+            }
+
+            let (lottery, cost) = prepare_purchase(&env, lottery_id, num_tickets);
+            if let Some((first, _, _)) = lotteries.first() {
+                if first.payment_token != lottery.payment_token {
+                    panic!("Mixed payment tokens");
+                }
+            }
+
+            total_cost += cost;
+            lotteries.push_back((lottery, num_tickets, cost));
+        }
+
+        let payment_token = lotteries.first().unwrap().0.payment_token;
+        token::Client::new(&env, &payment_token).transfer(
+            &buyer,
+            &env.current_contract_address(),
+            &total_cost,
+        );
+
+        for (mut lottery, num_tickets, cost) in lotteries.iter() {
+            record_purchase(&env, &mut lottery, &buyer, &buyer, num_tickets, cost, None);
+        }
+    }
+
+    /// Replace the prize metadata before any ticket is sold (only admin)
+    pub fn update_prize(env: Env, lottery_id: u64, nft_prize: NFTMetadata) {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.status != LotteryStatus::Open {
+            panic!("Lottery not active");
+        }
+
+        if lottery.tickets_sold > 0 {
+            panic!("Tickets already sold");
+        }
+
+        lottery.nft_prize = nft_prize.clone();
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+
+        env.events().publish(
+            (symbol_short!("update"), symbol_short!("prize"), lottery_id),
+            nft_prize,
+        );
+    }
+
+    /// Change the ticket price before any ticket is sold (only admin). The
+    /// price must stay positive; free entry is only set at creation.
+    pub fn update_ticket_price(env: Env, lottery_id: u64, ticket_price: i128) {
+        require_admin(&env);
+
+        if ticket_price <= 0 {
+            panic!("Invalid ticket price");
+        }
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.status != LotteryStatus::Open {
+            panic!("Lottery not active");
+        }
+
+        if lottery.tickets_sold > 0 {
+            panic!("Tickets already sold");
+        }
+
+        let old_price = lottery.ticket_price;
+        lottery.ticket_price = ticket_price;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+
+        env.events().publish(
+            (symbol_short!("update"), symbol_short!("price"), lottery_id),
+            (old_price, ticket_price),
+        );
+    }
+
+    /// Configure early-bird tiers and bundle discounts before any ticket is
+    /// sold (only admin)
+    pub fn set_pricing(env: Env, lottery_id: u64, pricing: PricingConfig) {
+        require_admin(&env);
+
+        let lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.status != LotteryStatus::Open {
+            panic!("Lottery not active");
+        }
+
+        if lottery.tickets_sold > 0 {
+            panic!("Tickets already sold");
+        }
+
+        if lottery.ticket_price == 0 {
+            panic!("Free-entry lottery requires a voucher");
+        }
+
+        if pricing.bundle_free > 0 && pricing.bundle_free >= pricing.bundle_size {
+            panic!("Invalid bundle");
+        }
+
+        if pricing.tiers.iter().any(|tier| tier.price <= 0) {
+            panic!("Invalid price");
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Pricing(lottery_id), &pricing);
+    }
+
+    /// Price the buyer would pay right now for `num_tickets` tickets
+    pub fn quote_price(env: Env, lottery_id: u64, num_tickets: u32) -> i128 {
+        let lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        quote(&env, &lottery, num_tickets)
+    }
+
+    /// Change the ticket supply (only admin). Once sales have begun the
+    /// supply can only grow, which reopens a sold-out lottery.
+    pub fn update_max_tickets(env: Env, lottery_id: u64, max_tickets: u32) {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if max_tickets == 0 {
+            panic!("Invalid max tickets");
+        }
+
+        if lottery.tickets_sold > 0 && max_tickets <= lottery.max_tickets {
+            panic!("Can only increase max tickets");
+        }
+
+        if max_tickets < lottery.min_tickets {
+            panic!("Minimum exceeds max tickets");
+        }
+
+        // Supply now always exceeds sales, so a sold-out lottery reopens
+        advance(&mut lottery, LotteryAction::AddTickets);
+
+        let old_max = lottery.max_tickets;
+        lottery.max_tickets = max_tickets;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+
+        env.events().publish(
+            (
+                symbol_short!("update"),
+                symbol_short!("max_tix"),
+                lottery_id,
+            ),
+            (old_max, max_tickets),
+        );
+    }
+
+    /// Set the weight of tickets issued to a participant from now on, e.g. 2
+    /// for loyalty NFT holders (only admin)
+    pub fn set_entry_weight(env: Env, participant: Address, weight: u32) {
+        require_admin(&env);
+
+        if weight == 0 || weight > MAX_ENTRY_WEIGHT {
+            panic!("Invalid weight");
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::EntryWeight(participant), &weight);
+    }
+
+    /// Set the ed25519 public key that signs free-entry vouchers (only admin)
+    pub fn set_voucher_signer(env: Env, signer: BytesN<32>) {
+        require_admin(&env);
+
+        env.storage()
+            .instance()
+            .set(&DataKey::VoucherSigner, &signer);
+    }
+
+    /// Claim free tickets for a zero-priced lottery with an admin-signed voucher.
+    /// The signature covers the XDR of `(lottery_id, recipient, count, nonce)`.
+    pub fn claim_free_ticket(
+        env: Env,
+        recipient: Address,
+        lottery_id: u64,
+        count: u32,
+        nonce: u64,
+        signature: BytesN<64>,
+    ) {
+        recipient.require_auth();
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        require_not_paused(&env, &lottery);
+        advance(&mut lottery, LotteryAction::Buy);
+        require_sales_open(&env, &lottery);
+
+        if lottery.ticket_price != 0 {
+            panic!("Lottery is not free-entry");
+        }
+
+        if count == 0 {
+            panic!("Invalid ticket count");
+        }
+
+        if lottery.tickets_sold + count > lottery.max_tickets {
+            panic!("Not enough tickets available");
+        }
+
+        let nonce_key = DataKey::VoucherNonce(nonce);
+        if env.storage().persistent().has(&nonce_key) {
+            panic!("Voucher already used");
+        }
+
+        let signer: BytesN<32> = env
+            .storage()
+            .instance()
+            .get(&DataKey::VoucherSigner)
+            .expect("Voucher signer not set");
+        // Bound to this deployment so a voucher can't be replayed elsewhere
+        let message = (
+            env.current_contract_address(),
+            lottery_id,
+            recipient.clone(),
+            count,
+            nonce,
+        )
+            .to_xdr(&env);
+        env.crypto().ed25519_verify(&signer, &message, &signature);
+
+        env.storage().persistent().set(&nonce_key, &true);
+
+        issue_tickets(&env, &mut lottery, &recipient, count);
+    }
+
+    /// Transfer one of your tickets while the lottery is still open
+    pub fn transfer_ticket(
+        env: Env,
+        from: Address,
+        to: Address,
+        lottery_id: u64,
+        ticket_number: u32,
+    ) {
+        from.require_auth();
+
+        move_ticket(&env, &from, &to, lottery_id, ticket_number);
+    }
+
+    /// Transfer a ticket on the owner's behalf, e.g. from a marketplace
+    /// contract approved with `approve` or `set_approval_for_all`
+    pub fn transfer_ticket_from(
+        env: Env,
+        operator: Address,
+        from: Address,
+        to: Address,
+        lottery_id: u64,
+        ticket_number: u32,
+    ) {
+        operator.require_auth();
+
+        require_spender(&env, &operator, &from, lottery_id, ticket_number);
+        move_ticket(&env, &from, &to, lottery_id, ticket_number);
+    }
+
+    /// Allow or revoke an operator moving all of the owner's tickets
+    pub fn set_approval_for_all(env: Env, owner: Address, operator: Address, approved: bool) {
+        owner.require_auth();
+
+        let key = DataKey::Operator(owner, operator);
+        if approved {
+            env.storage().persistent().set(&key, &true);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+    }
+
+    /// Check whether an operator may move all of the owner's tickets
+    pub fn is_approved_for_all(env: Env, owner: Address, operator: Address) -> bool {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Operator(owner, operator))
+            .unwrap_or(false)
+    }
+
+    /// Ticket NFT interface: current owner of a ticket
+    pub fn owner_of(env: Env, ticket_id: TicketId) -> Address {
+        let all_tickets: Vec<Ticket> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Tickets(ticket_id.lottery_id))
+            .unwrap();
+
+        if ticket_id.ticket_number == 0 || ticket_id.ticket_number > all_tickets.len() {
+            panic!("Ticket not found");
+        }
+
+        all_tickets.get(ticket_id.ticket_number - 1).unwrap().owner
+    }
+
+    /// Ticket NFT interface: number of tickets held across all lotteries
+    pub fn balance(env: Env, owner: Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::TicketBalance(owner))
+            .unwrap_or(0)
+    }
+
+    /// Ticket NFT interface: transfer a ticket you own
+    pub fn transfer(env: Env, from: Address, to: Address, ticket_id: TicketId) {
+        Self::transfer_ticket(env, from, to, ticket_id.lottery_id, ticket_id.ticket_number);
+    }
+
+    /// Ticket NFT interface: transfer a ticket as an approved spender
+    pub fn transfer_from(
+        env: Env,
+        spender: Address,
+        from: Address,
+        to: Address,
+        ticket_id: TicketId,
+    ) {
+        Self::transfer_ticket_from(
+            env,
+            spender,
+            from,
+            to,
+            ticket_id.lottery_id,
+            ticket_id.ticket_number,
+        );
+    }
+
+    /// Ticket NFT interface: approve one spender for a ticket, or clear it with `None`
+    pub fn approve(env: Env, owner: Address, spender: Option<Address>, ticket_id: TicketId) {
+        owner.require_auth();
+
+        if Self::owner_of(env.clone(), ticket_id.clone()) != owner {
+            panic!("Not ticket owner");
+        }
+
+        let key = DataKey::TicketApproval(ticket_id.lottery_id, ticket_id.ticket_number);
+        match spender {
+            Some(spender) => env.storage().persistent().set(&key, &spender),
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Ticket NFT interface: spender approved for a ticket, if any
+    pub fn get_approved(env: Env, ticket_id: TicketId) -> Option<Address> {
+        env.storage().persistent().get(&DataKey::TicketApproval(
+            ticket_id.lottery_id,
+            ticket_id.ticket_number,
+        ))
+    }
+
+    /// Draw winner (only admin)
+    pub fn draw_winner(env: Env, lottery_id: u64) -> Address {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        require_not_paused(&env, &lottery);
+        advance(&mut lottery, LotteryAction::Draw);
+        update_stats(&env, |stats| stats.active_lotteries -= 1);
+        consume_approval(&env, lottery_id, &ApprovalAction::Draw);
+
+        if lottery.tickets_sold == 0 {
+            panic!("No tickets sold");
+        }
+
+        if lottery.tickets_sold < lottery.min_tickets {
+            panic!("Minimum tickets not reached");
+        }
+
+        let winning_ticket = pick_ticket(&env, lottery_id, &Vec::new(&env));
+        record_draw(&env, &mut lottery, &winning_ticket);
+        reserve_consolation(&env, &mut lottery);
+        settle_referrals(&env, &lottery);
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+
+        // Drawing the current round of an active series opens the next one
+        roll_series(&env, &lottery);
+
+        winning_ticket.owner
+    }
+
+    /// Draw a new winner when the current one is ineligible or forfeited,
+    /// excluding every previously drawn ticket (only admin)
+    pub fn redraw(env: Env, lottery_id: u64) -> Address {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        require_not_paused(&env, &lottery);
+        advance(&mut lottery, LotteryAction::Redraw);
+
+        let draws: Vec<DrawRecord> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Draws(lottery_id))
+            .unwrap();
+        let mut excluded: Vec<u32> = Vec::new(&env);
+        for draw in draws.iter() {
+            excluded.push_back(draw.ticket_number);
+        }
+
+        // Holders who already took a consolation prize can't also win
+        let all_tickets: Vec<Ticket> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Tickets(lottery_id))
+            .unwrap();
+        for ticket in all_tickets.iter() {
+            if env
+                .storage()
+                .persistent()
+                .has(&DataKey::ConsolationClaimed(ticket.owner, lottery_id))
+            {
+                excluded.push_back(ticket.ticket_number);
+            }
+        }
+
+        let winning_ticket = pick_ticket(&env, lottery_id, &excluded);
+        record_draw(&env, &mut lottery, &winning_ticket);
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+
+        winning_ticket.owner
+    }
+
+    /// Claim the prize of a drawn lottery before the claim deadline.
+    /// Mega winners also receive the pot plus the rolled-over jackpot.
+    pub fn claim_prize(env: Env, winner: Address, lottery_id: u64) {
+        winner.require_auth();
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.winner != Some(winner.clone()) {
+            panic!("Not the winner");
+        }
+
+        advance(&mut lottery, LotteryAction::Claim);
+
+        if env.ledger().timestamp() > lottery.claim_deadline {
+            panic!("Claim period expired");
+        }
+
+        if lottery.is_mega {
+            let prize = lottery.pot + lottery.jackpot;
+            if prize > 0 {
+                token::Client::new(&env, &lottery.payment_token).transfer(
+                    &env.current_contract_address(),
+                    &winner,
+                    &prize,
+                );
+            }
+            lottery.pot = 0;
+            lottery.jackpot = 0;
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+    }
+
+    /// Configure the consolation reward for non-winners before the draw
+    /// (only admin)
+    pub fn set_consolation(env: Env, lottery_id: u64, consolation: Consolation) {
+        require_admin(&env);
+
+        let lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.status != LotteryStatus::Open && lottery.status != LotteryStatus::SoldOut {
+            panic!("Lottery already ended");
+        }
+
+        match &consolation {
+            Consolation::Nft(nft) => {
+                if nft.rarity != 1 {
+                    panic!("Consolation NFT must be Common");
+                }
+            }
+            Consolation::Rebate(amount) => {
+                if *amount <= 0 {
+                    panic!("Invalid rebate");
+                }
+            }
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Consolation(lottery_id), &consolation);
+    }
+
+    /// Claim the consolation reward as a participant who did not win
+    pub fn claim_consolation(env: Env, participant: Address, lottery_id: u64) -> Consolation {
+        participant.require_auth();
+
+        let lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.winner.is_none() {
+            panic!("Winner not drawn");
+        }
+
+        if env.ledger().timestamp() > lottery.claim_deadline {
+            panic!("Claim period expired");
+        }
+
+        let consolation: Consolation = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Consolation(lottery_id))
+            .expect("No consolation prize");
+
+        let claimed_key = DataKey::ConsolationClaimed(participant.clone(), lottery_id);
+        if env.storage().persistent().has(&claimed_key) {
+            panic!("Consolation already claimed");
+        }
+
+        let user_tickets: Vec<u32> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::UserTickets(participant.clone(), lottery_id))
+            .unwrap_or(Vec::new(&env));
+        if user_tickets.is_empty() {
+            panic!("No tickets");
+        }
+
+        let draws: Vec<DrawRecord> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Draws(lottery_id))
+            .unwrap();
+        // Every drawn ticket is excluded, so a winner replaced by a redraw
+        // can't come back for a consolation prize
+        if draws
+            .iter()
+            .any(|draw| user_tickets.contains(draw.ticket_number))
+        {
+            panic!("Winner cannot claim consolation");
+        }
+
+        env.storage().persistent().set(&claimed_key, &true);
+
+        if let Consolation::Rebate(amount) = consolation {
+            let reserve_key = DataKey::ConsolationReserve(lottery_id);
+            let reserve: i128 = env.storage().persistent().get(&reserve_key).unwrap();
+            if reserve < amount {
+                panic!("Insufficient pot");
+            }
+            env.storage()
+                .persistent()
+                .set(&reserve_key, &(reserve - amount));
+
+            token::Client::new(&env, &lottery.payment_token).transfer(
+                &env.current_contract_address(),
+                &participant,
+                &amount,
+            );
+        }
+
+        env.events().publish(
+            (symbol_short!("consol"), participant, lottery_id),
+            consolation.clone(),
+        );
+
+        consolation
+    }
+
+    /// Return consolation rebates nobody claimed within the claim period to
+    /// the pot (only admin)
+    pub fn release_consolation_reserve(env: Env, lottery_id: u64) -> i128 {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.winner.is_none() {
+            panic!("Winner not drawn");
+        }
+
+        if env.ledger().timestamp() <= lottery.claim_deadline {
+            panic!("Claim period not over");
+        }
+
+        let reserve_key = DataKey::ConsolationReserve(lottery_id);
+        let reserve: i128 = env.storage().persistent().get(&reserve_key).unwrap_or(0);
+        if reserve == 0 {
+            panic!("Nothing to release");
+        }
+
+        env.storage().persistent().set(&reserve_key, &0i128);
+        lottery.pot += reserve;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+
+        reserve
+    }
+
+    /// Take back a prize the winner did not claim in time (only admin).
+    /// A mega prize rolls into the progressive jackpot.
+    pub fn reclaim_prize(env: Env, lottery_id: u64) {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        advance(&mut lottery, LotteryAction::Reclaim);
+
+        if env.ledger().timestamp() <= lottery.claim_deadline {
+            panic!("Claim period not over");
+        }
+
+        if lottery.is_mega {
+            add_to_jackpot(&env, &lottery.payment_token, lottery.pot + lottery.jackpot);
+            lottery.pot = 0;
+            lottery.jackpot = 0;
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+    }
+
+    /// Make a lottery part of the mega series and seed it with the current
+    /// jackpot for its payment token (only admin, before sales begin)
+    pub fn set_mega(env: Env, lottery_id: u64, min_tickets: u32) {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.status != LotteryStatus::Open {
+            panic!("Lottery not active");
+        }
+
+        if lottery.is_mega {
+            panic!("Already a mega lottery");
+        }
+
+        if lottery.tickets_sold > 0 {
+            panic!("Tickets already sold");
+        }
+
+        if min_tickets > lottery.max_tickets {
+            panic!("Minimum exceeds max tickets");
+        }
+
+        let jackpot_key = DataKey::Jackpot(lottery.payment_token.clone());
+        let jackpot: i128 = env.storage().persistent().get(&jackpot_key).unwrap_or(0);
+        env.storage().persistent().set(&jackpot_key, &0i128);
+
+        lottery.is_mega = true;
+        lottery.min_tickets = min_tickets;
+        lottery.jackpot = jackpot;
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+    }
+
+    /// Close a mega lottery that missed its minimum, rolling its pot and
+    /// jackpot into the progressive jackpot (only admin)
+    pub fn close_failed_lottery(env: Env, lottery_id: u64) {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        advance(&mut lottery, LotteryAction::Fail);
+        update_stats(&env, |stats| stats.active_lotteries -= 1);
+
+        if !lottery.is_mega {
+            panic!("Not a mega lottery");
+        }
+
+        if lottery.tickets_sold >= lottery.min_tickets {
+            panic!("Minimum tickets reached");
+        }
+
+        add_to_jackpot(&env, &lottery.payment_token, lottery.pot + lottery.jackpot);
+
+        lottery.pot = 0;
+        lottery.jackpot = 0;
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+        settle_referrals(&env, &lottery);
+        roll_series(&env, &lottery);
+    }
+
+    /// Cancel a lottery before it is drawn (only admin). Buyers get back
+    /// what they paid, less platform fees, through `claim_refund`.
+    pub fn cancel_lottery(env: Env, lottery_id: u64) {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+        cancel(&env, &mut lottery);
+    }
+
+    /// Refund a buyer's payments (net of platform fees) for a cancelled
+    /// lottery, including any referral rewards that were held back
+    pub fn claim_refund(env: Env, buyer: Address, lottery_id: u64) -> i128 {
+        buyer.require_auth();
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.status != LotteryStatus::Cancelled {
+            panic!("Lottery not cancelled");
+        }
+
+        let contribution_key = DataKey::Contribution(buyer.clone(), lottery_id);
+        let contribution: i128 = env
+            .storage()
+            .persistent()
+            .get(&contribution_key)
+            .unwrap_or(0);
+        let paid_key = DataKey::ReferralPaid(buyer.clone(), lottery_id);
+        let referral_paid: i128 = env.storage().persistent().get(&paid_key).unwrap_or(0);
+        let amount = contribution + referral_paid;
+        if amount == 0 {
+            panic!("Nothing to refund");
+        }
+
+        env.storage().persistent().set(&contribution_key, &0i128);
+        env.storage().persistent().remove(&paid_key);
+        lottery.pot -= contribution;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+
+        token::Client::new(&env, &lottery.payment_token).transfer(
+            &env.current_contract_address(),
+            &buyer,
+            &amount,
+        );
+
+        amount
+    }
+
+    /// Get the progressive jackpot waiting for the next mega lottery
+    pub fn get_jackpot(env: Env, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Jackpot(token))
+            .unwrap_or(0)
+    }
+
+    /// Queue a sensitive admin change; it can be executed once the timelock
+    /// delay has passed (only admin). Returns the action hash.
+    pub fn queue_action(env: Env, action: TimelockAction) -> BytesN<32> {
+        require_admin(&env);
+
+        match &action {
+            TimelockAction::SetFee(fee_bps, _)
+            | TimelockAction::SetLotteryFee(_, Some(fee_bps))
+                if *fee_bps > MAX_FEE_BPS =>
+            {
+                panic!("Fee too high");
+            }
+            TimelockAction::SetReferralBps(referral_bps) if *referral_bps > MAX_REFERRAL_BPS => {
+                panic!("Referral reward too high");
+            }
+            TimelockAction::SetDelay(delay) if *delay > MAX_TIMELOCK_DELAY => {
+                panic!("Delay too long");
+            }
+            TimelockAction::SetClaimPeriod(claim_period) if *claim_period > MAX_CLAIM_PERIOD => {
+                panic!("Claim period too long");
+            }
+            TimelockAction::SetPaymentToken(token) => require_token(&env, token),
+            _ => {}
+        }
+
+        let hash = env.crypto().sha256(&action.clone().to_xdr(&env)).to_bytes();
+        let key = DataKey::Queued(hash.clone());
+        if env.storage().persistent().has(&key) {
+            panic!("Action already queued");
+        }
+
+        let delay: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TimelockDelay)
+            .unwrap_or(DEFAULT_TIMELOCK_DELAY);
+        let queued = QueuedAction {
+            action,
+            eta: env
+                .ledger()
+                .timestamp()
+                .checked_add(delay)
+                .expect("Delay too long"),
+        };
+        env.storage().persistent().set(&key, &queued);
+
+        env.events()
+            .publish((symbol_short!("queued"), hash.clone()), queued);
+
+        hash
+    }
+
+    /// Drop a queued action before it runs (only admin)
+    pub fn cancel_action(env: Env, action_hash: BytesN<32>) {
+        require_admin(&env);
+
+        let key = DataKey::Queued(action_hash.clone());
+        if !env.storage().persistent().has(&key) {
+            panic!("Action not queued");
+        }
+        env.storage().persistent().remove(&key);
+
+        env.events()
+            .publish((symbol_short!("unqueued"), action_hash), ());
+    }
+
+    /// Apply a queued action whose delay has passed (only admin)
+    pub fn execute_action(env: Env, action_hash: BytesN<32>) {
+        require_admin(&env);
+
+        let key = DataKey::Queued(action_hash.clone());
+        let queued: QueuedAction = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Action not queued");
+
+        if env.ledger().timestamp() < queued.eta {
+            panic!("Timelock not expired");
+        }
+        env.storage().persistent().remove(&key);
+
+        match queued.action.clone() {
+            TimelockAction::SetFee(fee_bps, recipient) => {
+                env.storage().instance().set(&DataKey::FeeBps, &fee_bps);
+                env.storage()
+                    .instance()
+                    .set(&DataKey::FeeRecipient, &recipient);
+            }
+            TimelockAction::SetLotteryFee(lottery_id, fee_bps) => {
+                let mut lottery: Lottery = env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::Lottery(lottery_id))
+                    .unwrap();
+                lottery.fee_bps = fee_bps;
+                env.storage()
+                    .persistent()
+                    .set(&DataKey::Lottery(lottery_id), &lottery);
+            }
+            TimelockAction::SetReferralBps(referral_bps) => {
+                env.storage()
+                    .instance()
+                    .set(&DataKey::ReferralBps, &referral_bps);
+            }
+            TimelockAction::SetClaimPeriod(claim_period) => {
+                env.storage()
+                    .instance()
+                    .set(&DataKey::ClaimPeriod, &claim_period);
+            }
+            TimelockAction::SetPaymentToken(token) => {
+                env.storage().instance().set(&DataKey::PaymentToken, &token);
+            }
+            TimelockAction::SetAdmin(admin) => {
+                env.storage().instance().set(&DataKey::Admin, &admin);
+            }
+            TimelockAction::Upgrade(wasm_hash) => {
+                env.deployer().update_current_contract_wasm(wasm_hash);
+            }
+            TimelockAction::SetDelay(delay) => {
+                env.storage()
+                    .instance()
+                    .set(&DataKey::TimelockDelay, &delay);
+            }
+        }
+
+        env.events()
+            .publish((symbol_short!("applied"), action_hash), queued.action);
+    }
+
+    /// Get a queued action by hash
+    pub fn get_queued_action(env: Env, action_hash: BytesN<32>) -> Option<QueuedAction> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Queued(action_hash))
+    }
+
+    /// Send a referrer's accumulated rewards in `token` to them
+    pub fn claim_referral_rewards(env: Env, referrer: Address, token: Address) -> i128 {
+        referrer.require_auth();
+
+        let rewards_key = DataKey::ReferralRewards(referrer.clone(), token.clone());
+        let amount: i128 = env.storage().persistent().get(&rewards_key).unwrap_or(0);
+        if amount == 0 {
+            panic!("No rewards to claim");
+        }
+
+        env.storage().persistent().set(&rewards_key, &0i128);
+        token::Client::new(&env, &token).transfer(
+            &env.current_contract_address(),
+            &referrer,
+            &amount,
+        );
+
+        amount
+    }
+
+    /// Get a referrer's unclaimed rewards in a payment token
+    pub fn get_referral_rewards(env: Env, referrer: Address, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::ReferralRewards(referrer, token))
+            .unwrap_or(0)
+    }
+
+    /// Get how many purchases and tickets an address has referred
+    pub fn get_referral_stats(env: Env, referrer: Address) -> ReferralStats {
+        env.storage()
+            .persistent()
+            .get(&DataKey::ReferralStats(referrer))
+            .unwrap_or_default()
+    }
+
+    /// Get contract-wide totals: lotteries, tickets, volume, fees and participants
+    pub fn get_stats(env: Env) -> ContractStats {
+        let mut stats = load_stats(&env);
+        stats.unique_participants = env
+            .storage()
+            .instance()
+            .get(&DataKey::ParticipantCount)
+            .unwrap_or(0);
+
+        stats
+    }
+
+    /// Get lifetime tickets bought, lotteries entered and wins for an address
+    pub fn get_user_stats(env: Env, user: Address) -> UserStats {
+        env.storage()
+            .persistent()
+            .get(&DataKey::UserStats(user))
+            .unwrap_or_default()
+    }
+
+    /// Get up to `limit` of the top participants, ranked by tickets bought,
+    /// then wins. Only the top `LEADERBOARD_SIZE` are tracked.
+    pub fn get_leaderboard(env: Env, limit: u32) -> Vec<(Address, UserStats)> {
+        let board: Vec<(Address, UserStats)> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Leaderboard)
+            .unwrap_or(Vec::new(&env));
+        board.slice(0..limit.min(board.len()))
+    }
+
+    /// Get up to `limit` participants in order of first entry, starting at
+    /// index `start`
+    pub fn get_participants(env: Env, start: u32, limit: u32) -> Vec<Address> {
+        let count: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ParticipantCount)
+            .unwrap_or(0);
+
+        let mut participants = Vec::new(&env);
+        for index in start..count.min(start.saturating_add(limit)) {
+            participants.push_back(
+                env.storage()
+                    .persistent()
+                    .get(&DataKey::Participant(index))
+                    .unwrap(),
+            );
+        }
+        participants
+    }
+
+    /// Send all accumulated fees in `token` to the fee recipient
+    pub fn claim_fees(env: Env, token: Address) -> i128 {
+        let recipient: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::FeeRecipient)
+            .expect("Fee recipient not set");
+        recipient.require_auth();
+
+        let fees_key = DataKey::AccumulatedFees(token.clone());
+        let amount: i128 = env.storage().persistent().get(&fees_key).unwrap_or(0);
+        if amount == 0 {
+            panic!("No fees to claim");
+        }
+
+        env.storage().persistent().set(&fees_key, &0i128);
+        token::Client::new(&env, &token).transfer(
+            &env.current_contract_address(),
+            &recipient,
+            &amount,
+        );
+
+        amount
+    }
+
+    /// Get unclaimed platform fees for a payment token
+    pub fn get_accumulated_fees(env: Env, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AccumulatedFees(token))
+            .unwrap_or(0)
+    }
+
+    /// Send a settled lottery's remaining pot to `recipient` (only admin)
+    pub fn withdraw_pot(env: Env, lottery_id: u64, recipient: Address) -> i128 {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+
+        if lottery.status != LotteryStatus::Claimed && lottery.status != LotteryStatus::Reclaimed {
+            panic!("Prize not settled");
+        }
+
+        let amount = lottery.pot;
+        if amount == 0 {
+            panic!("Nothing to withdraw");
+        }
+
+        consume_approval(
+            &env,
+            lottery_id,
+            &ApprovalAction::Withdraw(recipient.clone()),
+        );
+
+        lottery.pot = 0;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+
+        token::Client::new(&env, &lottery.payment_token).transfer(
+            &env.current_contract_address(),
+            &recipient,
+            &amount,
+        );
+
+        env.events()
+            .publish((symbol_short!("withdraw"), lottery_id), (recipient, amount));
+
+        amount
+    }
+
+    /// Set the addresses that approve guarded actions and how many of them
+    /// must sign off (only admin). Once approvers exist, replacing them needs
+    /// a `SetApprovers` proposal approved by the current set.
+    pub fn set_approvers(env: Env, approvers: Vec<Address>, threshold: u32) {
+        require_admin(&env);
+
+        if env.storage().instance().has(&DataKey::Approvers) {
+            require_approval(
+                &env,
+                APPROVERS_PROPOSAL_ID,
+                &ApprovalAction::SetApprovers(approvers.clone(), threshold),
+            );
+        }
+
+        if threshold == 0 || threshold > approvers.len() {
+            panic!("Invalid threshold");
+        }
+        for (i, approver) in approvers.iter().enumerate() {
+            if approvers.first_index_of(&approver) != Some(i as u32) {
+                panic!("Duplicate approver");
+            }
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::Approvers, &approvers);
+        env.storage()
+            .instance()
+            .set(&DataKey::ApprovalThreshold, &threshold);
+    }
+
+    /// Set how long a proposal stays open for approvals (only admin)
+    pub fn set_approval_window(env: Env, approval_window: u64) {
+        require_admin(&env);
+
+        env.storage()
+            .instance()
+            .set(&DataKey::ApprovalWindow, &approval_window);
+    }
+
+    /// Require approver sign-off before drawing or withdrawing from a
+    /// lottery (only admin). Lifting the requirement needs a
+    /// `DisableApproval` proposal and is blocked until the prize is settled.
+    pub fn set_requires_approval(env: Env, lottery_id: u64, required: bool) {
+        require_admin(&env);
+
+        if required && !env.storage().instance().has(&DataKey::Approvers) {
+            panic!("No approvers configured");
+        }
+
+        if !required && requires_approval(&env, lottery_id) {
+            let lottery: Lottery = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Lottery(lottery_id))
+                .unwrap();
+            if matches!(
+                lottery.status,
+                LotteryStatus::Open | LotteryStatus::SoldOut | LotteryStatus::Drawn
+            ) {
+                panic!("Lottery in progress");
+            }
+
+            require_approval(&env, lottery_id, &ApprovalAction::DisableApproval);
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::RequiresApproval(lottery_id), &required);
+    }
+
+    /// Open a proposal for `action` on a lottery, counting the proposer's
+    /// approval. Returns the action hash used to approve it.
+    pub fn propose_action(
+        env: Env,
+        approver: Address,
+        lottery_id: u64,
+        action: ApprovalAction,
+    ) -> BytesN<32> {
+        require_approver(&env, &approver);
+
+        let hash = action_hash(&env, &action);
+        let key = DataKey::Proposal(lottery_id, hash.clone());
+        if let Some(existing) = env.storage().persistent().get::<_, Proposal>(&key) {
+            if env.ledger().timestamp() <= existing.expires_at {
+                panic!("Proposal already open");
+            }
+        }
+
+        let approval_window: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ApprovalWindow)
+            .unwrap_or(DEFAULT_APPROVAL_WINDOW);
+        let proposal = Proposal {
+            action,
+            approvals: Vec::from_array(&env, [approver.clone()]),
+            expires_at: env.ledger().timestamp() + approval_window,
+        };
+        env.storage().persistent().set(&key, &proposal);
+
+        env.events().publish(
+            (symbol_short!("proposed"), lottery_id, approver),
+            hash.clone(),
+        );
+
+        hash
+    }
+
+    /// Add an approval to an open proposal
+    pub fn approve_action(env: Env, approver: Address, lottery_id: u64, action_hash: BytesN<32>) {
+        require_approver(&env, &approver);
+
+        let key = DataKey::Proposal(lottery_id, action_hash.clone());
+        let mut proposal: Proposal = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Proposal not found");
+
+        if env.ledger().timestamp() > proposal.expires_at {
+            panic!("Proposal expired");
+        }
+        if proposal.approvals.contains(&approver) {
+            panic!("Already approved");
+        }
+
+        proposal.approvals.push_back(approver.clone());
+        env.storage().persistent().set(&key, &proposal);
+
+        env.events().publish(
+            (symbol_short!("approved"), lottery_id, approver),
+            action_hash,
+        );
+    }
+
+    /// Get an open or expired proposal by lottery and action hash
+    pub fn get_proposal(env: Env, lottery_id: u64, action_hash: BytesN<32>) -> Option<Proposal> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Proposal(lottery_id, action_hash))
+    }
+
+    /// Halt ticket sales and draws for every lottery (only admin)
+    pub fn pause(env: Env) {
+        require_admin(&env);
+
+        env.storage().instance().set(&DataKey::Paused, &true);
+    }
+
+    /// Resume ticket sales and draws (only admin)
+    pub fn unpause(env: Env) {
+        require_admin(&env);
+
+        env.storage().instance().set(&DataKey::Paused, &false);
+    }
+
+    /// Halt ticket sales and draws for one lottery (only admin)
+    pub fn pause_lottery(env: Env, lottery_id: u64) {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+        lottery.paused = true;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+    }
+
+    /// Resume ticket sales and draws for one lottery (only admin)
+    pub fn unpause_lottery(env: Env, lottery_id: u64) {
+        require_admin(&env);
+
+        let mut lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+        lottery.paused = false;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Lottery(lottery_id), &lottery);
+    }
+
+    /// Check whether the whole contract is paused
+    pub fn is_paused(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::Paused)
+            .unwrap_or(false)
+    }
+
+    /// Get the current admin address
+    pub fn get_admin(env: Env) -> Address {
+        env.storage().instance().get(&DataKey::Admin).unwrap()
+    }
+
+    /// Get lottery details
+    pub fn get_lottery(env: Env, lottery_id: u64) -> Lottery {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap()
+    }
+
+    /// Get every draw made for a lottery, oldest first
+    pub fn get_draw_history(env: Env, lottery_id: u64) -> Vec<DrawRecord> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Draws(lottery_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Get the prize NFT metadata in the layout marketplaces expect, with the
+    /// rarity exposed as a "Rarity" trait
+    pub fn get_prize_metadata(env: Env, lottery_id: u64) -> MarketplaceMetadata {
+        let lottery: Lottery = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Lottery(lottery_id))
+            .unwrap();
+        let prize = lottery.nft_prize;
+
+        let mut attributes = Vec::new(&env);
+        attributes.push_back(NFTAttribute {
+            trait_type: String::from_str(&env, "Rarity"),
+            value: rarity_name(&env, prize.rarity),
+        });
+        attributes.append(&prize.attributes);
+
+        MarketplaceMetadata {
+            name: prize.name,
+            description: prize.description,
+            image: prize.image_url,
+            external_url: String::from_str(&env, ""),
+            token_uri: prize.token_uri,
+            collection: prize.collection_id,
+            attributes,
+        }
+    }
+
+    /// Get a lottery series and its current round
+    pub fn get_series(env: Env, series_id: u64) -> LotterySeries {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Series(series_id))
             .unwrap()
     }
 
@@ -1,7 +1,19 @@
 #![cfg(test)]
 
+extern crate std;
+
 use super::*;
-use soroban_sdk::{testutils::Address as _, token, Address, Env, String};
+use ed25519_dalek::{Signer, SigningKey};
+use soroban_sdk::{
+    symbol_short,
+    testutils::{
+        Address as _, AuthorizedFunction, AuthorizedInvocation, Events, Ledger, MockAuth,
+        MockAuthInvoke,
+    },
+    token,
+    xdr::ToXdr,
+    Address, BytesN, Env, IntoVal, String, Symbol,
+};
 
 fn create_token_contract<'a>(
     e: &Env,
@@ -35,6 +47,27 @@ fn setup_test_env<'a>() -> (
     (env, admin, user1, token_address, token_client, token_admin)
 }
 
+fn sign_voucher(
+    env: &Env,
+    contract_id: &Address,
+    signer: &SigningKey,
+    lottery_id: u64,
+    recipient: &Address,
+    count: u32,
+    nonce: u64,
+) -> BytesN<64> {
+    let message = (
+        contract_id.clone(),
+        lottery_id,
+        recipient.clone(),
+        count,
+        nonce,
+    )
+        .to_xdr(env);
+    let message: std::vec::Vec<u8> = message.iter().collect();
+    BytesN::from_array(env, &signer.sign(&message).to_bytes())
+}
+
 #[test]
 fn test_initialize() {
     let (env, admin, _, token_address, _, _) = setup_test_env();
@@ -80,12 +113,12 @@ fn test_create_lottery() {
     let nft_rarity: u32 = 4;
 
     let lottery_id = client.create_lottery(
-        &admin,
         &ticket_price,
         &max_tickets,
         &nft_name,
         &nft_image,
         &nft_rarity,
+        &None,
     );
 
     assert_eq!(lottery_id, 1);
@@ -96,10 +129,11 @@ fn test_create_lottery() {
     assert_eq!(lottery.ticket_price, 100);
     assert_eq!(lottery.max_tickets, 50);
     assert_eq!(lottery.tickets_sold, 0);
-    assert_eq!(lottery.is_active, true);
     assert_eq!(lottery.winner, None);
     assert_eq!(lottery.nft_prize.name, nft_name);
     assert_eq!(lottery.nft_prize.rarity, 4);
+    assert_eq!(lottery.payment_token, token_address);
+    assert_eq!(lottery.status, LotteryStatus::Open);
 }
 
 #[test]
@@ -112,22 +146,22 @@ fn test_create_multiple_lotteries() {
 
     // Create first lottery
     let lottery_id1 = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     // Create second lottery
     let lottery_id2 = client.create_lottery(
-        &admin,
         &50,
         &100,
         &String::from_str(&env, "Phoenix NFT"),
         &String::from_str(&env, "https://example.com/phoenix.png"),
         &3,
+        &None,
     );
 
     assert_eq!(lottery_id1, 1);
@@ -138,7 +172,7 @@ fn test_create_multiple_lotteries() {
 }
 
 #[test]
-#[should_panic(expected = "Unauthorized")]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
 fn test_create_lottery_unauthorized() {
     let (env, admin, user1, token_address, _, _) = setup_test_env();
     let contract_id = env.register_contract(None, NFTLotteryContract);
@@ -146,15 +180,29 @@ fn test_create_lottery_unauthorized() {
 
     client.initialize(&admin, &token_address);
 
-    // Try to create lottery as non-admin user - should fail
-    client.create_lottery(
-        &user1,
-        &100,
-        &50,
-        &String::from_str(&env, "Dragon NFT"),
-        &String::from_str(&env, "https://example.com/dragon.png"),
-        &4,
-    );
+    let name = String::from_str(&env, "Dragon NFT");
+    let image = String::from_str(&env, "https://example.com/dragon.png");
+
+    // Only a non-admin user signs - should fail
+    client
+        .mock_auths(&[MockAuth {
+            address: &user1,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "create_lottery",
+                args: (
+                    100_i128,
+                    50_u32,
+                    name.clone(),
+                    image.clone(),
+                    4_u32,
+                    None::<Address>,
+                )
+                    .into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .create_lottery(&100, &50, &name, &image, &4, &None);
 }
 
 #[test]
@@ -167,12 +215,12 @@ fn test_buy_single_ticket() {
 
     // Create lottery
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     let initial_balance = token_client.balance(&user1);
@@ -194,6 +242,39 @@ fn test_buy_single_ticket() {
     assert_eq!(final_balance, initial_balance - 100);
 }
 
+#[test]
+fn test_buy_ticket_with_lottery_token() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let (usdc_address, usdc_client, usdc_admin) = create_token_contract(&env);
+    usdc_admin.mint(&user1, &100000);
+
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    // Lottery priced in a different token than the default
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &Some(usdc_address.clone()),
+    );
+
+    let lottery = client.get_lottery(&lottery_id);
+    assert_eq!(lottery.payment_token, usdc_address);
+
+    client.buy_ticket(&user1, &lottery_id, &2);
+
+    // Payment taken in the lottery's token only
+    assert_eq!(usdc_client.balance(&user1), 100000 - 200);
+    assert_eq!(usdc_client.balance(&contract_id), 200);
+    assert_eq!(token_client.balance(&user1), 100000);
+    assert_eq!(token_client.balance(&contract_id), 0);
+}
+
 #[test]
 fn test_buy_multiple_tickets() {
     let (env, admin, user1, token_address, token_client, _) = setup_test_env();
@@ -203,12 +284,12 @@ fn test_buy_multiple_tickets() {
     client.initialize(&admin, &token_address);
 
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     let initial_balance = token_client.balance(&user1);
@@ -243,12 +324,12 @@ fn test_multiple_users_buy_tickets() {
     client.initialize(&admin, &token_address);
 
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     // User1 buys 3 tickets
@@ -279,12 +360,12 @@ fn test_buy_more_than_available() {
     client.initialize(&admin, &token_address);
 
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &10, // Only 10 tickets available
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     // Try to buy 15 tickets - should fail
@@ -305,12 +386,12 @@ fn test_draw_winner() {
     client.initialize(&admin, &token_address);
 
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     // Users buy tickets
@@ -318,19 +399,19 @@ fn test_draw_winner() {
     client.buy_ticket(&user2, &lottery_id, &2);
 
     // Draw winner
-    let winner = client.draw_winner(&admin, &lottery_id);
+    let winner = client.draw_winner(&lottery_id);
 
     // Verify winner is one of the participants
     assert!(winner == user1 || winner == user2);
 
     // Verify lottery is no longer active
     let lottery = client.get_lottery(&lottery_id);
-    assert_eq!(lottery.is_active, false);
+    assert_eq!(lottery.status, LotteryStatus::Drawn);
     assert_eq!(lottery.winner, Some(winner));
 }
 
 #[test]
-#[should_panic(expected = "Unauthorized")]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
 fn test_draw_winner_unauthorized() {
     let (env, admin, user1, token_address, _, _) = setup_test_env();
     let contract_id = env.register_contract(None, NFTLotteryContract);
@@ -339,18 +420,28 @@ fn test_draw_winner_unauthorized() {
     client.initialize(&admin, &token_address);
 
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     client.buy_ticket(&user1, &lottery_id, &1);
 
     // Non-admin tries to draw winner - should fail
-    client.draw_winner(&user1, &lottery_id);
+    client
+        .mock_auths(&[MockAuth {
+            address: &user1,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "draw_winner",
+                args: (lottery_id,).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .draw_winner(&lottery_id);
 }
 
 #[test]
@@ -363,16 +454,16 @@ fn test_draw_winner_no_tickets() {
     client.initialize(&admin, &token_address);
 
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     // Try to draw winner with no tickets sold - should fail
-    client.draw_winner(&admin, &lottery_id);
+    client.draw_winner(&lottery_id);
 }
 
 #[test]
@@ -385,19 +476,19 @@ fn test_draw_winner_twice() {
     client.initialize(&admin, &token_address);
 
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     client.buy_ticket(&user1, &lottery_id, &1);
-    client.draw_winner(&admin, &lottery_id);
+    client.draw_winner(&lottery_id);
 
     // Try to draw winner again - should fail
-    client.draw_winner(&admin, &lottery_id);
+    client.draw_winner(&lottery_id);
 }
 
 #[test]
@@ -410,16 +501,16 @@ fn test_buy_ticket_after_draw() {
     client.initialize(&admin, &token_address);
 
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     client.buy_ticket(&user1, &lottery_id, &1);
-    client.draw_winner(&admin, &lottery_id);
+    client.draw_winner(&lottery_id);
 
     // Try to buy ticket after winner drawn - should fail
     client.buy_ticket(&user1, &lottery_id, &1);
@@ -434,12 +525,12 @@ fn test_get_user_tickets_no_tickets() {
     client.initialize(&admin, &token_address);
 
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &50,
         &String::from_str(&env, "Dragon NFT"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     // User hasn't bought any tickets
@@ -465,12 +556,12 @@ fn test_complete_lottery_flow() {
 
     // 2. Create lottery
     let lottery_id = client.create_lottery(
-        &admin,
         &100,
         &10,
         &String::from_str(&env, "Legendary Dragon"),
         &String::from_str(&env, "https://example.com/dragon.png"),
         &4,
+        &None,
     );
 
     // 3. Multiple users buy tickets
@@ -481,14 +572,14 @@ fn test_complete_lottery_flow() {
     // 4. Verify all tickets sold
     let lottery = client.get_lottery(&lottery_id);
     assert_eq!(lottery.tickets_sold, 10);
-    assert_eq!(lottery.is_active, true);
+    assert_eq!(lottery.status, LotteryStatus::SoldOut);
 
     // 5. Draw winner
-    let winner = client.draw_winner(&admin, &lottery_id);
+    let winner = client.draw_winner(&lottery_id);
 
     // 6. Verify final state
     let final_lottery = client.get_lottery(&lottery_id);
-    assert_eq!(final_lottery.is_active, false);
+    assert_eq!(final_lottery.status, LotteryStatus::Drawn);
     assert_eq!(final_lottery.winner, Some(winner.clone()));
 
     // 7. Verify winner is one of the participants
@@ -498,3 +589,3166 @@ fn test_complete_lottery_flow() {
     let contract_balance = token_client.balance(&contract_id);
     assert_eq!(contract_balance, 1000); // 10 tickets * 100
 }
+
+#[test]
+fn test_claim_free_ticket() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let signer = SigningKey::from_bytes(&[7u8; 32]);
+    client.set_voucher_signer(&BytesN::from_array(
+        &env,
+        &signer.verifying_key().to_bytes(),
+    ));
+
+    // Giveaway lottery priced at zero
+    let lottery_id = client.create_lottery(
+        &0,
+        &100,
+        &String::from_str(&env, "Giveaway NFT"),
+        &String::from_str(&env, "https://example.com/giveaway.png"),
+        &1,
+        &None,
+    );
+
+    let signature = sign_voucher(&env, &contract_id, &signer, lottery_id, &user1, 2, 1);
+    client.claim_free_ticket(&user1, &lottery_id, &2, &1, &signature);
+
+    let lottery = client.get_lottery(&lottery_id);
+    assert_eq!(lottery.tickets_sold, 2);
+
+    let user_tickets = client.get_user_tickets(&user1, &lottery_id);
+    assert_eq!(user_tickets.len(), 2);
+
+    // No payment was taken
+    assert_eq!(token_client.balance(&user1), 100000);
+}
+
+#[test]
+#[should_panic(expected = "Voucher already used")]
+fn test_claim_free_ticket_replay() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let signer = SigningKey::from_bytes(&[7u8; 32]);
+    client.set_voucher_signer(&BytesN::from_array(
+        &env,
+        &signer.verifying_key().to_bytes(),
+    ));
+
+    let lottery_id = client.create_lottery(
+        &0,
+        &100,
+        &String::from_str(&env, "Giveaway NFT"),
+        &String::from_str(&env, "https://example.com/giveaway.png"),
+        &1,
+        &None,
+    );
+
+    let signature = sign_voucher(&env, &contract_id, &signer, lottery_id, &user1, 1, 42);
+    client.claim_free_ticket(&user1, &lottery_id, &1, &42, &signature);
+
+    // Reusing the same voucher - should fail
+    client.claim_free_ticket(&user1, &lottery_id, &1, &42, &signature);
+}
+
+#[test]
+#[should_panic]
+fn test_claim_free_ticket_bad_signature() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let signer = SigningKey::from_bytes(&[7u8; 32]);
+    client.set_voucher_signer(&BytesN::from_array(
+        &env,
+        &signer.verifying_key().to_bytes(),
+    ));
+
+    let lottery_id = client.create_lottery(
+        &0,
+        &100,
+        &String::from_str(&env, "Giveaway NFT"),
+        &String::from_str(&env, "https://example.com/giveaway.png"),
+        &1,
+        &None,
+    );
+
+    // Voucher issued to user1 cannot be redeemed by user2
+    let signature = sign_voucher(&env, &contract_id, &signer, lottery_id, &user1, 1, 1);
+    client.claim_free_ticket(&user2, &lottery_id, &1, &1, &signature);
+}
+
+#[test]
+#[should_panic(expected = "Free-entry lottery requires a voucher")]
+fn test_buy_ticket_free_lottery() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &0,
+        &100,
+        &String::from_str(&env, "Giveaway NFT"),
+        &String::from_str(&env, "https://example.com/giveaway.png"),
+        &1,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+}
+
+#[test]
+fn test_platform_fee_accounting() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let fee_recipient = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    // 2.5% fee
+    set_fee(&env, &client, 250, &fee_recipient);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    // 4 tickets * 100 = 400, fee = 10
+    client.buy_ticket(&user1, &lottery_id, &4);
+
+    let lottery = client.get_lottery(&lottery_id);
+    assert_eq!(lottery.pot, 390);
+    assert_eq!(client.get_accumulated_fees(&token_address), 10);
+
+    let claimed = client.claim_fees(&token_address);
+    assert_eq!(claimed, 10);
+    assert_eq!(token_client.balance(&fee_recipient), 10);
+    assert_eq!(token_client.balance(&contract_id), 390);
+    assert_eq!(client.get_accumulated_fees(&token_address), 0);
+}
+
+#[test]
+fn test_platform_fee_rounds_down() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let fee_recipient = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    set_fee(&env, &client, 250, &fee_recipient);
+
+    let lottery_id = client.create_lottery(
+        &33,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    // 3 * 33 = 99, 2.5% = 2.475 -> 2
+    client.buy_ticket(&user1, &lottery_id, &3);
+    assert_eq!(client.get_accumulated_fees(&token_address), 2);
+    assert_eq!(client.get_lottery(&lottery_id).pot, 97);
+
+    // 1 * 33 = 33, 2.5% = 0.825 -> 0
+    client.buy_ticket(&user1, &lottery_id, &1);
+    assert_eq!(client.get_accumulated_fees(&token_address), 2);
+    assert_eq!(client.get_lottery(&lottery_id).pot, 130);
+}
+
+#[test]
+fn test_lottery_fee_override() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let fee_recipient = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    set_fee(&env, &client, 250, &fee_recipient);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    // Fee-free promo lottery
+    run_timelocked(
+        &env,
+        &client,
+        TimelockAction::SetLotteryFee(lottery_id, Some(0)),
+    );
+    client.buy_ticket(&user1, &lottery_id, &2);
+    assert_eq!(client.get_accumulated_fees(&token_address), 0);
+    assert_eq!(client.get_lottery(&lottery_id).pot, 200);
+
+    // 10% override
+    run_timelocked(
+        &env,
+        &client,
+        TimelockAction::SetLotteryFee(lottery_id, Some(1_000)),
+    );
+    client.buy_ticket(&user1, &lottery_id, &1);
+    assert_eq!(client.get_accumulated_fees(&token_address), 10);
+
+    // Back to the global fee
+    run_timelocked(
+        &env,
+        &client,
+        TimelockAction::SetLotteryFee(lottery_id, None),
+    );
+    client.buy_ticket(&user1, &lottery_id, &2);
+    assert_eq!(client.get_accumulated_fees(&token_address), 15);
+}
+
+#[test]
+#[should_panic(expected = "Fee too high")]
+fn test_set_fee_above_max() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    client.queue_action(&TimelockAction::SetFee(MAX_FEE_BPS + 1, admin));
+}
+
+#[test]
+fn test_mega_jackpot_rollover() {
+    let (env, admin, user1, token_address, token_client, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    // First mega round needs 5 tickets but only sells 3
+    let first_id = client.create_lottery(
+        &100,
+        &10,
+        &String::from_str(&env, "Mega Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    client.set_mega(&first_id, &5);
+    client.buy_ticket(&user1, &first_id, &3);
+
+    client.close_failed_lottery(&first_id);
+    assert_eq!(client.get_jackpot(&token_address), 300);
+
+    let first = client.get_lottery(&first_id);
+    assert_eq!(first.status, LotteryStatus::Failed);
+    assert_eq!(first.winner, None);
+    assert_eq!(first.pot, 0);
+
+    // Next mega round is seeded with the jackpot
+    let second_id = client.create_lottery(
+        &100,
+        &10,
+        &String::from_str(&env, "Mega Phoenix"),
+        &String::from_str(&env, "https://example.com/phoenix.png"),
+        &4,
+        &None,
+    );
+    client.set_mega(&second_id, &2);
+    assert_eq!(client.get_jackpot(&token_address), 0);
+    assert_eq!(client.get_lottery(&second_id).jackpot, 300);
+
+    client.buy_ticket(&user2, &second_id, &2);
+
+    // Winner receives the pot plus the rolled-over jackpot on claim
+    let winner = client.draw_winner(&second_id);
+    assert_eq!(winner, user2);
+    assert_eq!(token_client.balance(&contract_id), 500);
+
+    client.claim_prize(&user2, &second_id);
+    assert_eq!(token_client.balance(&user2), 100000 - 200 + 500);
+    assert_eq!(token_client.balance(&contract_id), 0);
+}
+
+#[test]
+#[should_panic(expected = "Minimum tickets not reached")]
+fn test_mega_draw_below_minimum() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &10,
+        &String::from_str(&env, "Mega Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    client.set_mega(&lottery_id, &5);
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    client.draw_winner(&lottery_id);
+}
+
+#[test]
+#[should_panic(expected = "Minimum tickets reached")]
+fn test_close_failed_lottery_after_minimum() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &10,
+        &String::from_str(&env, "Mega Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    client.set_mega(&lottery_id, &2);
+    client.buy_ticket(&user1, &lottery_id, &2);
+
+    client.close_failed_lottery(&lottery_id);
+}
+
+#[test]
+fn test_claim_prize() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    env.ledger().with_mut(|li| li.timestamp = 1_000);
+    let winner = client.draw_winner(&lottery_id);
+
+    let lottery = client.get_lottery(&lottery_id);
+    assert_eq!(lottery.status, LotteryStatus::Drawn);
+    assert_eq!(lottery.claim_deadline, 1_000 + DEFAULT_CLAIM_PERIOD);
+
+    client.claim_prize(&winner, &lottery_id);
+
+    let lottery = client.get_lottery(&lottery_id);
+    assert_eq!(lottery.status, LotteryStatus::Claimed);
+}
+
+#[test]
+#[should_panic(expected = "Prize already claimed")]
+fn test_claim_prize_twice() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    let winner = client.draw_winner(&lottery_id);
+
+    client.claim_prize(&winner, &lottery_id);
+    client.claim_prize(&winner, &lottery_id);
+}
+
+#[test]
+#[should_panic(expected = "Claim period expired")]
+fn test_claim_prize_late() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    run_timelocked(&env, &client, TimelockAction::SetClaimPeriod(3_600));
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    let winner = client.draw_winner(&lottery_id);
+
+    env.ledger().with_mut(|li| li.timestamp += 3_601);
+    client.claim_prize(&winner, &lottery_id);
+}
+
+#[test]
+#[should_panic(expected = "Not the winner")]
+fn test_claim_prize_not_winner() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.draw_winner(&lottery_id);
+
+    client.claim_prize(&user2, &lottery_id);
+}
+
+#[test]
+fn test_reclaim_unclaimed_mega_prize() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    run_timelocked(&env, &client, TimelockAction::SetClaimPeriod(3_600));
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &10,
+        &String::from_str(&env, "Mega Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    client.set_mega(&lottery_id, &1);
+    client.buy_ticket(&user1, &lottery_id, &2);
+    client.draw_winner(&lottery_id);
+
+    env.ledger().with_mut(|li| li.timestamp += 3_601);
+    client.reclaim_prize(&lottery_id);
+
+    // Unclaimed mega prize rolls into the jackpot
+    let lottery = client.get_lottery(&lottery_id);
+    assert_eq!(lottery.status, LotteryStatus::Reclaimed);
+    assert_eq!(lottery.pot, 0);
+    assert_eq!(client.get_jackpot(&token_address), 200);
+}
+
+#[test]
+#[should_panic(expected = "Claim period not over")]
+fn test_reclaim_prize_too_early() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.draw_winner(&lottery_id);
+
+    client.reclaim_prize(&lottery_id);
+}
+
+#[test]
+fn test_redraw_excludes_previous_winner() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+
+    let first_winner = client.draw_winner(&lottery_id);
+    let second_winner = client.redraw(&lottery_id);
+
+    // Only one other ticket is left to draw
+    assert_ne!(first_winner, second_winner);
+    assert_eq!(
+        client.get_lottery(&lottery_id).winner,
+        Some(second_winner.clone())
+    );
+
+    let history = client.get_draw_history(&lottery_id);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history.get(0).unwrap().winner, first_winner);
+    assert_eq!(history.get(1).unwrap().winner, second_winner);
+    assert_ne!(
+        history.get(0).unwrap().ticket_number,
+        history.get(1).unwrap().ticket_number
+    );
+
+    // The new winner can claim, the old one cannot
+    client.claim_prize(&second_winner, &lottery_id);
+}
+
+#[test]
+#[should_panic(expected = "No eligible tickets")]
+fn test_redraw_no_eligible_tickets() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.draw_winner(&lottery_id);
+
+    client.redraw(&lottery_id);
+}
+
+#[test]
+#[should_panic(expected = "Prize already settled")]
+fn test_redraw_after_claim() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &2);
+    let winner = client.draw_winner(&lottery_id);
+    client.claim_prize(&winner, &lottery_id);
+
+    client.redraw(&lottery_id);
+}
+
+#[test]
+fn test_lottery_transition_table() {
+    use LotteryAction::*;
+    use LotteryStatus::*;
+
+    let statuses = [Open, SoldOut, Drawn, Claimed, Reclaimed, Cancelled, Failed];
+    let actions = [
+        Buy, SellOut, AddTickets, Draw, Redraw, Claim, Reclaim, Cancel, Fail,
+    ];
+
+    // Every allowed transition; anything not listed must be rejected
+    let allowed = [
+        (Open, Buy, Open),
+        (Open, SellOut, SoldOut),
+        (Open, AddTickets, Open),
+        (Open, Draw, Drawn),
+        (Open, Cancel, Cancelled),
+        (Open, Fail, Failed),
+        (SoldOut, AddTickets, Open),
+        (SoldOut, Draw, Drawn),
+        (SoldOut, Cancel, Cancelled),
+        (SoldOut, Fail, Failed),
+        (Drawn, Redraw, Drawn),
+        (Drawn, Claim, Claimed),
+        (Drawn, Reclaim, Reclaimed),
+    ];
+
+    for status in statuses {
+        for action in actions {
+            let expected = allowed
+                .iter()
+                .find(|(from, via, _)| *from == status && *via == action)
+                .map(|(_, _, to)| *to);
+            assert_eq!(
+                try_transition(status, action).ok(),
+                expected,
+                "{:?} --{:?}-->",
+                status,
+                action
+            );
+        }
+    }
+}
+
+#[test]
+fn test_cancel_lottery_and_refund() {
+    let (env, admin, user1, token_address, token_client, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let fee_recipient = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    set_fee(&env, &client, 1_000, &fee_recipient);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &3);
+    client.buy_ticket(&user2, &lottery_id, &1);
+
+    client.cancel_lottery(&lottery_id);
+    assert_eq!(
+        client.get_lottery(&lottery_id).status,
+        LotteryStatus::Cancelled
+    );
+
+    // Refunds are net of the 10% platform fee
+    assert_eq!(client.claim_refund(&user1, &lottery_id), 270);
+    assert_eq!(client.claim_refund(&user2, &lottery_id), 90);
+    assert_eq!(token_client.balance(&user1), 100000 - 300 + 270);
+    assert_eq!(token_client.balance(&user2), 100000 - 100 + 90);
+    assert_eq!(client.get_lottery(&lottery_id).pot, 0);
+}
+
+#[test]
+#[should_panic(expected = "Nothing to refund")]
+fn test_claim_refund_twice() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.cancel_lottery(&lottery_id);
+
+    client.claim_refund(&user1, &lottery_id);
+    client.claim_refund(&user1, &lottery_id);
+}
+
+#[test]
+#[should_panic(expected = "Lottery already ended")]
+fn test_cancel_drawn_lottery() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.draw_winner(&lottery_id);
+
+    client.cancel_lottery(&lottery_id);
+}
+
+#[test]
+#[should_panic(expected = "Contract paused")]
+fn test_buy_ticket_while_paused() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.pause();
+    assert!(client.is_paused());
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+}
+
+#[test]
+#[should_panic(expected = "Lottery paused")]
+fn test_draw_winner_lottery_paused() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.pause_lottery(&lottery_id);
+
+    client.draw_winner(&lottery_id);
+}
+
+#[test]
+fn test_pause_keeps_views_and_refunds() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &2);
+
+    client.pause();
+    client.pause_lottery(&lottery_id);
+
+    // Views still work
+    assert_eq!(client.get_lottery(&lottery_id).tickets_sold, 2);
+    assert_eq!(client.get_user_tickets(&user1, &lottery_id).len(), 2);
+
+    // Refunds still work
+    client.cancel_lottery(&lottery_id);
+    client.claim_refund(&user1, &lottery_id);
+    assert_eq!(token_client.balance(&user1), 100000);
+
+    client.unpause();
+    client.unpause_lottery(&lottery_id);
+    assert!(!client.is_paused());
+    assert!(!client.get_lottery(&lottery_id).paused);
+}
+
+#[test]
+fn test_series_opens_next_round_on_draw() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    env.ledger().with_mut(|li| li.timestamp = 1_000);
+    let series_id = client.create_series(
+        &100,
+        &20,
+        &604_800,
+        &String::from_str(&env, "Weekly Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &2,
+        &None,
+    );
+
+    let series = client.get_series(&series_id);
+    assert_eq!(series.current_round, 1);
+    let first_id = series.current_lottery_id;
+
+    let first = client.get_lottery(&first_id);
+    assert_eq!(first.series_id, Some(series_id));
+    assert_eq!(first.round, 1);
+    assert_eq!(first.ticket_price, 100);
+    assert_eq!(first.end_time, 1_000 + 604_800);
+
+    client.buy_ticket(&user1, &first_id, &2);
+
+    env.ledger().with_mut(|li| li.timestamp = 700_000);
+    client.draw_winner(&first_id);
+
+    // Round 2 opened from the same template
+    let series = client.get_series(&series_id);
+    assert_eq!(series.current_round, 2);
+    assert_ne!(series.current_lottery_id, first_id);
+
+    let second = client.get_lottery(&series.current_lottery_id);
+    assert_eq!(second.round, 2);
+    assert_eq!(second.status, LotteryStatus::Open);
+    assert_eq!(second.max_tickets, 20);
+    assert_eq!(second.nft_prize.name, first.nft_prize.name);
+    assert_eq!(second.end_time, 700_000 + 604_800);
+    assert_eq!(client.get_lottery_count(), 2);
+}
+
+#[test]
+fn test_end_series_stops_rounds() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let series_id = client.create_series(
+        &100,
+        &20,
+        &0,
+        &String::from_str(&env, "Weekly Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &2,
+        &None,
+    );
+    let lottery_id = client.get_series(&series_id).current_lottery_id;
+
+    client.end_series(&series_id);
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.draw_winner(&lottery_id);
+
+    let series = client.get_series(&series_id);
+    assert!(!series.is_active);
+    assert_eq!(series.current_round, 1);
+    assert_eq!(client.get_lottery_count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Sales closed")]
+fn test_buy_ticket_after_round_ends() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let series_id = client.create_series(
+        &100,
+        &20,
+        &3_600,
+        &String::from_str(&env, "Hourly Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &1,
+        &None,
+    );
+    let lottery_id = client.get_series(&series_id).current_lottery_id;
+
+    env.ledger().with_mut(|li| li.timestamp += 3_601);
+    client.buy_ticket(&user1, &lottery_id, &1);
+}
+
+#[test]
+fn test_create_lottery_with_metadata() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let mut attributes = Vec::new(&env);
+    attributes.push_back(NFTAttribute {
+        trait_type: String::from_str(&env, "Element"),
+        value: String::from_str(&env, "Fire"),
+    });
+
+    let nft_prize = NFTMetadata {
+        name: String::from_str(&env, "Dragon NFT"),
+        image_url: String::from_str(&env, "https://example.com/dragon.png"),
+        rarity: 4,
+        description: String::from_str(&env, "A fire-breathing dragon"),
+        attributes,
+        collection_id: Some(String::from_str(&env, "mythic-beasts")),
+        token_uri: String::from_str(&env, "ipfs://dragon.json"),
+    };
+
+    let lottery_id = client.create_lottery_with_metadata(&100, &50, &nft_prize, &None);
+
+    let metadata = client.get_prize_metadata(&lottery_id);
+    assert_eq!(metadata.name, String::from_str(&env, "Dragon NFT"));
+    assert_eq!(
+        metadata.description,
+        String::from_str(&env, "A fire-breathing dragon")
+    );
+    assert_eq!(
+        metadata.image,
+        String::from_str(&env, "https://example.com/dragon.png")
+    );
+    assert_eq!(metadata.external_url, String::from_str(&env, ""));
+    assert_eq!(
+        metadata.token_uri,
+        String::from_str(&env, "ipfs://dragon.json")
+    );
+    assert_eq!(
+        metadata.collection,
+        Some(String::from_str(&env, "mythic-beasts"))
+    );
+
+    // Rarity comes first, followed by the custom traits
+    assert_eq!(metadata.attributes.len(), 2);
+    let rarity = metadata.attributes.get(0).unwrap();
+    assert_eq!(rarity.trait_type, String::from_str(&env, "Rarity"));
+    assert_eq!(rarity.value, String::from_str(&env, "Legendary"));
+    let element = metadata.attributes.get(1).unwrap();
+    assert_eq!(element.trait_type, String::from_str(&env, "Element"));
+    assert_eq!(element.value, String::from_str(&env, "Fire"));
+}
+
+#[test]
+fn test_get_prize_metadata_basic_lottery() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Phoenix NFT"),
+        &String::from_str(&env, "https://example.com/phoenix.png"),
+        &2,
+        &None,
+    );
+
+    let metadata = client.get_prize_metadata(&lottery_id);
+    assert_eq!(metadata.name, String::from_str(&env, "Phoenix NFT"));
+    assert_eq!(metadata.description, String::from_str(&env, ""));
+    assert_eq!(metadata.collection, None);
+    assert_eq!(metadata.attributes.len(), 1);
+    assert_eq!(
+        metadata.attributes.get(0).unwrap().value,
+        String::from_str(&env, "Rare")
+    );
+}
+
+#[test]
+fn test_update_lottery_before_sales() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dargon NFT"),
+        &String::from_str(&env, "https://example.com/dargon.png"),
+        &4,
+        &None,
+    );
+
+    let mut nft_prize = client.get_lottery(&lottery_id).nft_prize;
+    nft_prize.name = String::from_str(&env, "Dragon NFT");
+    nft_prize.image_url = String::from_str(&env, "https://example.com/dragon.png");
+    client.update_prize(&lottery_id, &nft_prize);
+
+    client.update_ticket_price(&lottery_id, &150);
+    let event = env.events().all().last().unwrap();
+    assert_eq!(event.0, contract_id);
+    assert_eq!(
+        event.1,
+        (symbol_short!("update"), symbol_short!("price"), lottery_id).into_val(&env)
+    );
+
+    client.update_max_tickets(&lottery_id, &20);
+
+    let lottery = client.get_lottery(&lottery_id);
+    assert_eq!(lottery.nft_prize.name, String::from_str(&env, "Dragon NFT"));
+    assert_eq!(
+        lottery.nft_prize.image_url,
+        String::from_str(&env, "https://example.com/dragon.png")
+    );
+    assert_eq!(lottery.ticket_price, 150);
+    assert_eq!(lottery.max_tickets, 20);
+}
+
+#[test]
+#[should_panic(expected = "Tickets already sold")]
+fn test_update_ticket_price_after_sales() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.update_ticket_price(&lottery_id, &50);
+}
+
+#[test]
+#[should_panic(expected = "Can only increase max tickets")]
+fn test_decrease_max_tickets_after_sales() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.update_max_tickets(&lottery_id, &40);
+}
+
+#[test]
+fn test_increase_max_tickets_reopens_sold_out() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &2,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &2);
+    assert_eq!(
+        client.get_lottery(&lottery_id).status,
+        LotteryStatus::SoldOut
+    );
+
+    client.update_max_tickets(&lottery_id, &5);
+    let lottery = client.get_lottery(&lottery_id);
+    assert_eq!(lottery.status, LotteryStatus::Open);
+    assert_eq!(lottery.max_tickets, 5);
+
+    client.buy_ticket(&user1, &lottery_id, &3);
+    assert_eq!(client.get_lottery(&lottery_id).tickets_sold, 5);
+}
+
+#[test]
+fn test_transfer_ticket() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &3);
+    client.transfer_ticket(&user1, &user2, &lottery_id, &2);
+
+    let user1_tickets = client.get_user_tickets(&user1, &lottery_id);
+    assert_eq!(user1_tickets.len(), 2);
+    assert_eq!(user1_tickets.get(0).unwrap(), 1);
+    assert_eq!(user1_tickets.get(1).unwrap(), 3);
+
+    let user2_tickets = client.get_user_tickets(&user2, &lottery_id);
+    assert_eq!(user2_tickets.len(), 1);
+    assert_eq!(user2_tickets.get(0).unwrap(), 2);
+
+    // The new owner can win with the transferred ticket
+    client.transfer_ticket(&user1, &user2, &lottery_id, &1);
+    client.transfer_ticket(&user1, &user2, &lottery_id, &3);
+    assert_eq!(client.draw_winner(&lottery_id), user2);
+}
+
+#[test]
+#[should_panic(expected = "Not ticket owner")]
+fn test_transfer_ticket_not_owner() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+
+    client.transfer_ticket(&user2, &user2, &lottery_id, &1);
+}
+
+#[test]
+#[should_panic(expected = "Lottery not active")]
+fn test_transfer_ticket_after_draw() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.draw_winner(&lottery_id);
+
+    client.transfer_ticket(&user1, &user2, &lottery_id, &1);
+}
+
+#[test]
+fn test_transfer_ticket_from_approved_operator() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let marketplace = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    client.set_approval_for_all(&user1, &marketplace, &true);
+    assert!(client.is_approved_for_all(&user1, &marketplace));
+
+    client.transfer_ticket_from(&marketplace, &user1, &user2, &lottery_id, &1);
+    assert_eq!(client.get_user_tickets(&user1, &lottery_id).len(), 0);
+    assert_eq!(client.get_user_tickets(&user2, &lottery_id).len(), 1);
+
+    client.set_approval_for_all(&user1, &marketplace, &false);
+    assert!(!client.is_approved_for_all(&user1, &marketplace));
+}
+
+#[test]
+#[should_panic(expected = "Not approved")]
+fn test_transfer_ticket_from_unapproved_operator() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let marketplace = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    client.transfer_ticket_from(&marketplace, &user1, &user2, &lottery_id, &1);
+}
+
+#[test]
+fn test_ticket_nft_interface() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let first_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    let second_id = client.create_lottery(
+        &50,
+        &50,
+        &String::from_str(&env, "Phoenix NFT"),
+        &String::from_str(&env, "https://example.com/phoenix.png"),
+        &3,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &first_id, &2);
+    client.buy_ticket(&user1, &second_id, &1);
+    assert_eq!(client.balance(&user1), 3);
+
+    let ticket_id = TicketId {
+        lottery_id: first_id,
+        ticket_number: 2,
+    };
+    assert_eq!(client.owner_of(&ticket_id), user1);
+
+    client.transfer(&user1, &user2, &ticket_id);
+    assert_eq!(client.owner_of(&ticket_id), user2);
+    assert_eq!(client.balance(&user1), 2);
+    assert_eq!(client.balance(&user2), 1);
+    assert_eq!(client.get_user_tickets(&user2, &first_id).len(), 1);
+}
+
+#[test]
+fn test_ticket_approve_and_transfer_from() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let marketplace = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    let ticket_id = TicketId {
+        lottery_id,
+        ticket_number: 1,
+    };
+    client.approve(&user1, &Some(marketplace.clone()), &ticket_id);
+    assert_eq!(client.get_approved(&ticket_id), Some(marketplace.clone()));
+
+    client.transfer_from(&marketplace, &user1, &user2, &ticket_id);
+    assert_eq!(client.owner_of(&ticket_id), user2);
+
+    // Approval is cleared once the ticket changes hands
+    assert_eq!(client.get_approved(&ticket_id), None);
+}
+
+#[test]
+#[should_panic(expected = "Not approved")]
+fn test_ticket_transfer_from_revoked_approval() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let marketplace = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    let ticket_id = TicketId {
+        lottery_id,
+        ticket_number: 1,
+    };
+    client.approve(&user1, &Some(marketplace.clone()), &ticket_id);
+    client.approve(&user1, &None, &ticket_id);
+
+    client.transfer_from(&marketplace, &user1, &user2, &ticket_id);
+}
+
+#[test]
+fn test_buy_ticket_for_recipient() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let friend = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket_for(&user1, &friend, &lottery_id, &2);
+
+    // Payer is charged, recipient holds the tickets
+    assert_eq!(token_client.balance(&user1), 100000 - 200);
+    assert_eq!(token_client.balance(&friend), 0);
+    assert_eq!(client.get_user_tickets(&user1, &lottery_id).len(), 0);
+    assert_eq!(client.get_user_tickets(&friend, &lottery_id).len(), 2);
+    assert_eq!(client.balance(&friend), 2);
+
+    // The recipient wins, the payer gets any refund
+    assert_eq!(client.draw_winner(&lottery_id), friend);
+}
+
+#[test]
+fn test_buy_ticket_for_refunds_payer() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let friend = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket_for(&user1, &friend, &lottery_id, &2);
+    client.cancel_lottery(&lottery_id);
+
+    assert_eq!(client.claim_refund(&user1, &lottery_id), 200);
+    assert_eq!(token_client.balance(&user1), 100000);
+}
+
+fn create_lotteries(env: &Env, client: &NFTLotteryContractClient, count: u32) -> Vec<u64> {
+    let mut lottery_ids = Vec::new(env);
+    for _ in 0..count {
+        lottery_ids.push_back(client.create_lottery(
+            &100,
+            &50,
+            &String::from_str(env, "Dragon NFT"),
+            &String::from_str(env, "https://example.com/dragon.png"),
+            &4,
+            &None,
+        ));
+    }
+    lottery_ids
+}
+
+#[test]
+fn test_buy_tickets_batch() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_ids = create_lotteries(&env, &client, 3);
+
+    let mut purchases = Vec::new(&env);
+    purchases.push_back((lottery_ids.get(0).unwrap(), 1u32));
+    purchases.push_back((lottery_ids.get(1).unwrap(), 2u32));
+    purchases.push_back((lottery_ids.get(2).unwrap(), 3u32));
+    client.buy_tickets_batch(&user1, &purchases);
+
+    assert_eq!(token_client.balance(&user1), 100000 - 600);
+    assert_eq!(token_client.balance(&contract_id), 600);
+
+    for (lottery_id, num_tickets) in purchases.iter() {
+        assert_eq!(client.get_lottery(&lottery_id).tickets_sold, num_tickets);
+        assert_eq!(
+            client.get_user_tickets(&user1, &lottery_id).len(),
+            num_tickets
+        );
+    }
+}
+
+#[test]
+fn test_buy_tickets_batch_all_or_nothing() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_ids = create_lotteries(&env, &client, 2);
+
+    // Second entry asks for more tickets than exist
+    let mut purchases = Vec::new(&env);
+    purchases.push_back((lottery_ids.get(0).unwrap(), 1u32));
+    purchases.push_back((lottery_ids.get(1).unwrap(), 51u32));
+    assert!(client.try_buy_tickets_batch(&user1, &purchases).is_err());
+
+    // Nothing was charged or issued
+    assert_eq!(token_client.balance(&user1), 100000);
+    assert_eq!(
+        client
+            .get_lottery(&lottery_ids.get(0).unwrap())
+            .tickets_sold,
+        0
+    );
+}
+
+#[test]
+#[should_panic(expected = "Mixed payment tokens")]
+fn test_buy_tickets_batch_mixed_tokens() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let (usdc_address, _, _) = create_token_contract(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let first_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    let second_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Phoenix NFT"),
+        &String::from_str(&env, "https://example.com/phoenix.png"),
+        &3,
+        &Some(usdc_address),
+    );
+
+    let mut purchases = Vec::new(&env);
+    purchases.push_back((first_id, 1u32));
+    purchases.push_back((second_id, 1u32));
+    client.buy_tickets_batch(&user1, &purchases);
+}
+
+#[test]
+fn test_buy_tickets_batch_budget() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let batch_ids = create_lotteries(&env, &client, 10);
+    let single_ids = create_lotteries(&env, &client, 10);
+
+    let mut purchases = Vec::new(&env);
+    for lottery_id in batch_ids.iter() {
+        purchases.push_back((lottery_id, 5u32));
+    }
+
+    // Ten lotteries in one call stay within the default transaction budget
+    env.budget().reset_default();
+    client.buy_tickets_batch(&user1, &purchases);
+    let batch_cpu = env.budget().cpu_instruction_cost();
+
+    let mut single_cpu = 0;
+    for lottery_id in single_ids.iter() {
+        env.budget().reset_default();
+        client.buy_ticket(&user1, &lottery_id, &5);
+        single_cpu += env.budget().cpu_instruction_cost();
+    }
+
+    // One token transfer instead of ten makes the batch cheaper
+    assert!(batch_cpu < single_cpu);
+}
+
+#[test]
+fn test_bundle_pricing() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    // 5 tickets for the price of 4
+    client.set_pricing(
+        &lottery_id,
+        &PricingConfig {
+            tiers: Vec::new(&env),
+            bundle_size: 5,
+            bundle_free: 1,
+        },
+    );
+
+    assert_eq!(client.quote_price(&lottery_id, &4), 400);
+    assert_eq!(client.quote_price(&lottery_id, &5), 400);
+    assert_eq!(client.quote_price(&lottery_id, &7), 600);
+    assert_eq!(client.quote_price(&lottery_id, &10), 800);
+
+    client.buy_ticket(&user1, &lottery_id, &10);
+    assert_eq!(token_client.balance(&user1), 100000 - 800);
+    assert_eq!(client.get_lottery(&lottery_id).tickets_sold, 10);
+}
+
+#[test]
+fn test_early_bird_pricing_tiers() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    // First 3 tickets at 50, then 80 until the timestamp, then full price
+    let mut tiers = Vec::new(&env);
+    tiers.push_back(PriceTier {
+        max_sold: 3,
+        ends_at: 0,
+        price: 50,
+    });
+    tiers.push_back(PriceTier {
+        max_sold: 0,
+        ends_at: 1_000,
+        price: 80,
+    });
+    client.set_pricing(
+        &lottery_id,
+        &PricingConfig {
+            tiers,
+            bundle_size: 0,
+            bundle_free: 0,
+        },
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 500);
+
+    // A purchase crossing the first threshold mixes both tiers
+    assert_eq!(client.quote_price(&lottery_id, &5), 3 * 50 + 2 * 80);
+    client.buy_ticket(&user1, &lottery_id, &5);
+    assert_eq!(token_client.balance(&user1), 100000 - 310);
+
+    assert_eq!(client.quote_price(&lottery_id, &1), 80);
+
+    env.ledger().with_mut(|li| li.timestamp = 1_001);
+    assert_eq!(client.quote_price(&lottery_id, &2), 200);
+}
+
+#[test]
+#[should_panic(expected = "Tickets already sold")]
+fn test_set_pricing_after_sales() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.set_pricing(
+        &lottery_id,
+        &PricingConfig {
+            tiers: Vec::new(&env),
+            bundle_size: 5,
+            bundle_free: 1,
+        },
+    );
+}
+
+#[test]
+fn test_referral_rewards() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let referrer = Address::generate(&env);
+    let fee_recipient = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    set_fee(&env, &client, 250, &fee_recipient);
+    run_timelocked(&env, &client, TimelockAction::SetReferralBps(500));
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    // 400 purchase: 10 fee, 20 referral reward, 370 to the pot
+    client.buy_ticket_with_referrer(&user1, &lottery_id, &4, &referrer);
+    client.buy_ticket_with_referrer(&user1, &lottery_id, &2, &referrer);
+
+    assert_eq!(client.get_lottery(&lottery_id).pot, 370 + 185);
+    // Rewards become claimable once the lottery is drawn
+    assert_eq!(client.get_referral_rewards(&referrer, &token_address), 0);
+    client.draw_winner(&lottery_id);
+    assert_eq!(client.get_referral_rewards(&referrer, &token_address), 30);
+
+    let stats = client.get_referral_stats(&referrer);
+    assert_eq!(stats.referred_purchases, 2);
+    assert_eq!(stats.referred_tickets, 6);
+
+    assert_eq!(client.claim_referral_rewards(&referrer, &token_address), 30);
+    assert_eq!(token_client.balance(&referrer), 30);
+    assert_eq!(client.get_referral_rewards(&referrer, &token_address), 0);
+}
+
+#[test]
+#[should_panic(expected = "Self-referral not allowed")]
+fn test_self_referral() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    run_timelocked(&env, &client, TimelockAction::SetReferralBps(500));
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket_with_referrer(&user1, &lottery_id, &1, &user1);
+}
+
+#[test]
+#[should_panic(expected = "Referral reward too high")]
+fn test_set_referral_bps_above_max() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    client.queue_action(&TimelockAction::SetReferralBps(MAX_REFERRAL_BPS + 1));
+}
+
+#[test]
+fn test_weighted_pick_cumulative_ranges() {
+    let env = Env::default();
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let mut tickets = Vec::new(&env);
+    tickets.push_back(Ticket {
+        lottery_id: 1,
+        owner: user1.clone(),
+        ticket_number: 1,
+        weight: 2,
+    });
+    tickets.push_back(Ticket {
+        lottery_id: 1,
+        owner: user2.clone(),
+        ticket_number: 2,
+        weight: 1,
+    });
+
+    // Ticket 1 covers [0, 2), ticket 2 covers [2, 3)
+    assert_eq!(weighted_pick(&tickets, 0).ticket_number, 1);
+    assert_eq!(weighted_pick(&tickets, 1).ticket_number, 1);
+    assert_eq!(weighted_pick(&tickets, 2).ticket_number, 2);
+}
+
+#[test]
+fn test_weighted_draw_distribution() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    // user1 holds the loyalty NFT and gets double weight
+    client.set_entry_weight(&user1, &2);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+
+    // Simulate many draws: user1 should win about 2/3 of the time
+    env.budget().reset_unlimited();
+    let draws = 3_000;
+    let mut user1_wins = 0;
+    env.as_contract(&contract_id, || {
+        for _ in 0..draws {
+            if pick_ticket(&env, lottery_id, &Vec::new(&env)).owner == user1 {
+                user1_wins += 1;
+            }
+        }
+    });
+
+    let share = user1_wins as f64 / draws as f64;
+    assert!(share > 0.62 && share < 0.71, "user1 won {}", share);
+}
+
+#[test]
+fn test_weighted_draw_equal_weights() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    // 3 tickets vs 1 ticket at the default weight: 3/4 vs 1/4
+    client.buy_ticket(&user1, &lottery_id, &3);
+    client.buy_ticket(&user2, &lottery_id, &1);
+
+    env.budget().reset_unlimited();
+    let draws = 3_000;
+    let mut user1_wins = 0;
+    env.as_contract(&contract_id, || {
+        for _ in 0..draws {
+            if pick_ticket(&env, lottery_id, &Vec::new(&env)).owner == user1 {
+                user1_wins += 1;
+            }
+        }
+    });
+
+    let share = user1_wins as f64 / draws as f64;
+    assert!(share > 0.71 && share < 0.79, "user1 won {}", share);
+}
+
+#[test]
+#[should_panic(expected = "Invalid weight")]
+fn test_set_entry_weight_zero() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    client.set_entry_weight(&user1, &0);
+}
+
+#[test]
+fn test_consolation_rebate() {
+    let (env, admin, user1, token_address, token_client, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let user3 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    token_admin.mint(&user3, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    client.set_consolation(&lottery_id, &Consolation::Rebate(20));
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+    client.buy_ticket(&user3, &lottery_id, &1);
+
+    let winner = client.draw_winner(&lottery_id);
+
+    // Every non-winner gets the rebate from the pot
+    let mut rebated = 0;
+    for participant in [user1, user2, user3] {
+        if participant == winner {
+            continue;
+        }
+        client.claim_consolation(&participant, &lottery_id);
+        assert_eq!(token_client.balance(&participant), 100000 - 100 + 20);
+        rebated += 20;
+    }
+
+    assert_eq!(rebated, 40);
+    assert_eq!(client.get_lottery(&lottery_id).pot, 300 - 40);
+}
+
+#[test]
+fn test_consolation_nft() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    let mut nft = client.get_lottery(&lottery_id).nft_prize;
+    nft.name = String::from_str(&env, "Dragon Egg");
+    nft.rarity = 1;
+    client.set_consolation(&lottery_id, &Consolation::Nft(nft));
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+
+    let winner = client.draw_winner(&lottery_id);
+    let loser = if winner == user1 { user2 } else { user1 };
+
+    match client.claim_consolation(&loser, &lottery_id) {
+        Consolation::Nft(nft) => assert_eq!(nft.name, String::from_str(&env, "Dragon Egg")),
+        Consolation::Rebate(_) => panic!("expected an NFT"),
+    }
+}
+
+#[test]
+#[should_panic(expected = "Winner cannot claim consolation")]
+fn test_consolation_winner() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    client.set_consolation(&lottery_id, &Consolation::Rebate(20));
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    let winner = client.draw_winner(&lottery_id);
+
+    client.claim_consolation(&winner, &lottery_id);
+}
+
+#[test]
+#[should_panic(expected = "Consolation already claimed")]
+fn test_consolation_claim_twice() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    client.set_consolation(&lottery_id, &Consolation::Rebate(20));
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+
+    let winner = client.draw_winner(&lottery_id);
+    let loser = if winner == user1 { user2 } else { user1 };
+
+    client.claim_consolation(&loser, &lottery_id);
+    client.claim_consolation(&loser, &lottery_id);
+}
+
+#[test]
+fn test_user_stats() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_ids = create_lotteries(&env, &client, 2);
+    client.buy_ticket(&user1, &lottery_ids.get(0).unwrap(), &2);
+    client.buy_ticket(&user1, &lottery_ids.get(0).unwrap(), &1);
+    client.buy_ticket(&user1, &lottery_ids.get(1).unwrap(), &4);
+
+    client.draw_winner(&lottery_ids.get(0).unwrap());
+
+    let stats = client.get_user_stats(&user1);
+    assert_eq!(stats.tickets_bought, 7);
+    assert_eq!(stats.lotteries_entered, 2);
+    assert_eq!(stats.wins, 1);
+
+    let stranger = client.get_user_stats(&Address::generate(&env));
+    assert_eq!(stranger.tickets_bought, 0);
+}
+
+#[test]
+fn test_redraw_moves_win() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+
+    client.draw_winner(&lottery_id);
+    client.redraw(&lottery_id);
+
+    let wins = client.get_user_stats(&user1).wins + client.get_user_stats(&user2).wins;
+    assert_eq!(wins, 1);
+}
+
+#[test]
+fn test_leaderboard() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let user3 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    token_admin.mint(&user3, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.buy_ticket(&user1, &lottery_id, &2);
+    client.buy_ticket(&user2, &lottery_id, &5);
+    client.buy_ticket(&user3, &lottery_id, &3);
+
+    let board = client.get_leaderboard(&2);
+    assert_eq!(board.len(), 2);
+    assert_eq!(board.get(0).unwrap().0, user2);
+    assert_eq!(board.get(1).unwrap().0, user3);
+
+    assert_eq!(client.get_leaderboard(&10).len(), 3);
+}
+
+#[test]
+fn test_contract_stats() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    let fee_recipient = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    set_fee(&env, &client, 500, &fee_recipient);
+
+    let lottery_ids = create_lotteries(&env, &client, 3);
+    client.buy_ticket(&user1, &lottery_ids.get(0).unwrap(), &2);
+    client.buy_ticket(&user2, &lottery_ids.get(0).unwrap(), &1);
+    client.buy_ticket(&user1, &lottery_ids.get(1).unwrap(), &4);
+
+    client.draw_winner(&lottery_ids.get(0).unwrap());
+    client.cancel_lottery(&lottery_ids.get(2).unwrap());
+
+    let stats = client.get_stats();
+    assert_eq!(stats.total_lotteries, 3);
+    assert_eq!(stats.active_lotteries, 1);
+    assert_eq!(stats.total_tickets, 7);
+    assert_eq!(stats.unique_participants, 2);
+    assert_eq!(stats.volume.get(token_address.clone()), Some(700));
+    assert_eq!(stats.fees.get(token_address), Some(35));
+}
+
+#[test]
+fn test_contract_stats_empty() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let stats = client.get_stats();
+    assert_eq!(stats.total_lotteries, 0);
+    assert_eq!(stats.total_tickets, 0);
+    assert!(stats.volume.is_empty());
+}
+
+#[test]
+fn test_initialize_requires_admin_auth() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    contract_id.clone(),
+                    Symbol::new(&env, "initialize"),
+                    (admin.clone(), token_address.clone()).into_val(&env),
+                )),
+                sub_invocations: std::vec![],
+            }
+        )]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_initialize_requires_admin_signature() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let (token_address, _, _) = create_token_contract(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    // No signature from `admin`
+    client.initialize(&admin, &token_address);
+}
+
+#[test]
+#[should_panic(expected = "Invalid payment token")]
+fn test_initialize_invalid_token() {
+    let (env, admin, _, _, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &Address::generate(&env));
+}
+
+mod admin_wallet {
+    use soroban_sdk::{
+        auth::{Context, CustomAccountInterface},
+        contract, contracterror, contractimpl,
+        crypto::Hash,
+        symbol_short, BytesN, Env, Vec,
+    };
+
+    #[contracterror]
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(u32)]
+    pub enum WalletError {
+        NotEnoughSignatures = 1,
+        BadSignerOrder = 2,
+    }
+
+    /// M-of-N ed25519 multisig account used as the lottery admin
+    #[contract]
+    pub struct AdminWallet;
+
+    #[contractimpl]
+    impl AdminWallet {
+        pub fn init(env: Env, signers: Vec<BytesN<32>>, threshold: u32) {
+            env.storage()
+                .instance()
+                .set(&symbol_short!("signers"), &signers);
+            env.storage()
+                .instance()
+                .set(&symbol_short!("thresh"), &threshold);
+        }
+    }
+
+    #[contractimpl]
+    impl CustomAccountInterface for AdminWallet {
+        type Signature = Vec<(u32, BytesN<64>)>;
+        type Error = WalletError;
+
+        #[allow(non_snake_case)]
+        fn __check_auth(
+            env: Env,
+            signature_payload: Hash<32>,
+            signatures: Vec<(u32, BytesN<64>)>,
+            _auth_contexts: Vec<Context>,
+        ) -> Result<(), WalletError> {
+            let signers: Vec<BytesN<32>> = env
+                .storage()
+                .instance()
+                .get(&symbol_short!("signers"))
+                .unwrap();
+            let threshold: u32 = env
+                .storage()
+                .instance()
+                .get(&symbol_short!("thresh"))
+                .unwrap();
+
+            if signatures.len() < threshold {
+                return Err(WalletError::NotEnoughSignatures);
+            }
+
+            let mut next_signer = 0;
+            for (index, signature) in signatures.iter() {
+                if index < next_signer {
+                    return Err(WalletError::BadSignerOrder);
+                }
+                next_signer = index + 1;
+                env.crypto().ed25519_verify(
+                    &signers.get(index).unwrap(),
+                    &signature_payload.clone().into(),
+                    &signature,
+                );
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Build an auth entry for `wallet` signed by `keys` (signer index, key)
+fn wallet_auth_entry(
+    env: &Env,
+    wallet: &Address,
+    keys: &[(u32, &SigningKey)],
+    contract: &Address,
+    fn_name: &str,
+    args: soroban_sdk::Vec<soroban_sdk::Val>,
+) -> soroban_sdk::xdr::SorobanAuthorizationEntry {
+    use soroban_sdk::xdr::{
+        Hash, HashIdPreimage, HashIdPreimageSorobanAuthorization, InvokeContractArgs, Limits,
+        ScSymbol, ScVal, SorobanAddressCredentials, SorobanAuthorizedFunction,
+        SorobanAuthorizedInvocation, SorobanCredentials, WriteXdr,
+    };
+    use soroban_sdk::TryFromVal;
+
+    let nonce = 7;
+    let signature_expiration_ledger = env.ledger().sequence() + 100;
+    let args: std::vec::Vec<ScVal> = args
+        .iter()
+        .map(|arg| ScVal::try_from_val(env, &arg).unwrap())
+        .collect();
+    let invocation = SorobanAuthorizedInvocation {
+        function: SorobanAuthorizedFunction::ContractFn(InvokeContractArgs {
+            contract_address: contract.into(),
+            function_name: ScSymbol(fn_name.try_into().unwrap()),
+            args: args.try_into().unwrap(),
+        }),
+        sub_invocations: Default::default(),
+    };
+
+    let preimage = HashIdPreimage::SorobanAuthorization(HashIdPreimageSorobanAuthorization {
+        network_id: Hash(env.ledger().network_id().to_array()),
+        nonce,
+        signature_expiration_ledger,
+        invocation: invocation.clone(),
+    });
+    let payload = env.crypto().sha256(&soroban_sdk::Bytes::from_slice(
+        env,
+        &preimage.to_xdr(Limits::none()).unwrap(),
+    ));
+
+    let mut signatures = soroban_sdk::Vec::<(u32, BytesN<64>)>::new(env);
+    for (index, key) in keys {
+        let signature = key.sign(&payload.to_array()).to_bytes();
+        signatures.push_back((*index, BytesN::from_array(env, &signature)));
+    }
+
+    soroban_sdk::xdr::SorobanAuthorizationEntry {
+        credentials: SorobanCredentials::Address(SorobanAddressCredentials {
+            address: wallet.into(),
+            nonce,
+            signature_expiration_ledger,
+            signature: ScVal::try_from_val(env, &signatures.to_val()).unwrap(),
+        }),
+        root_invocation: invocation,
+    }
+}
+
+fn setup_wallet_admin(env: &Env) -> (Address, [SigningKey; 3]) {
+    let keys = [
+        SigningKey::from_bytes(&[1; 32]),
+        SigningKey::from_bytes(&[2; 32]),
+        SigningKey::from_bytes(&[3; 32]),
+    ];
+    let wallet = env.register_contract(None, admin_wallet::AdminWallet);
+    let mut signers = soroban_sdk::Vec::new(env);
+    for key in keys.iter() {
+        signers.push_back(BytesN::from_array(env, &key.verifying_key().to_bytes()));
+    }
+    admin_wallet::AdminWalletClient::new(env, &wallet).init(&signers, &2);
+    (wallet, keys)
+}
+
+#[test]
+fn test_multisig_admin() {
+    let (env, _, _, token_address, _, _) = setup_test_env();
+    let (wallet, keys) = setup_wallet_admin(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&wallet, &token_address);
+    assert_eq!(client.get_admin(), wallet);
+
+    // 2-of-3 signatures from the wallet's signers authorize the admin call
+    env.set_auths(&[wallet_auth_entry(
+        &env,
+        &wallet,
+        &[(0, &keys[0]), (2, &keys[2])],
+        &contract_id,
+        "set_approval_window",
+        (3600_u64,).into_val(&env),
+    )]);
+    client.set_approval_window(&3600);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_multisig_admin_below_threshold() {
+    let (env, _, _, token_address, _, _) = setup_test_env();
+    let (wallet, keys) = setup_wallet_admin(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&wallet, &token_address);
+
+    env.set_auths(&[wallet_auth_entry(
+        &env,
+        &wallet,
+        &[(1, &keys[1])],
+        &contract_id,
+        "set_approval_window",
+        (3600_u64,).into_val(&env),
+    )]);
+    client.set_approval_window(&3600);
+}
+
+fn setup_approvals(env: &Env, client: &NFTLotteryContractClient, lottery_id: u64) -> [Address; 3] {
+    let approvers = [
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    ];
+    client.set_approvers(&soroban_sdk::Vec::from_array(env, approvers.clone()), &2);
+    client.set_requires_approval(&lottery_id, &true);
+    approvers
+}
+
+#[test]
+fn test_draw_with_approvals() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    let approvers = setup_approvals(&env, &client, lottery_id);
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    let hash = client.propose_action(&approvers[0], &lottery_id, &ApprovalAction::Draw);
+    client.approve_action(&approvers[2], &lottery_id, &hash);
+
+    assert_eq!(client.draw_winner(&lottery_id), user1);
+    // The proposal is consumed by the draw
+    assert!(client.get_proposal(&lottery_id, &hash).is_none());
+}
+
+#[test]
+#[should_panic(expected = "Not enough approvals")]
+fn test_draw_below_threshold() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    let approvers = setup_approvals(&env, &client, lottery_id);
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    client.propose_action(&approvers[0], &lottery_id, &ApprovalAction::Draw);
+    client.draw_winner(&lottery_id);
+}
+
+#[test]
+#[should_panic(expected = "Proposal expired")]
+fn test_approval_expired() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    let approvers = setup_approvals(&env, &client, lottery_id);
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    let hash = client.propose_action(&approvers[0], &lottery_id, &ApprovalAction::Draw);
+    env.ledger()
+        .with_mut(|li| li.timestamp += DEFAULT_APPROVAL_WINDOW + 1);
+    client.approve_action(&approvers[1], &lottery_id, &hash);
+}
+
+#[test]
+fn test_withdraw_pot_with_approvals() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let treasury = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    let approvers = setup_approvals(&env, &client, lottery_id);
+    client.buy_ticket(&user1, &lottery_id, &3);
+
+    let hash = client.propose_action(&approvers[1], &lottery_id, &ApprovalAction::Draw);
+    client.approve_action(&approvers[0], &lottery_id, &hash);
+    client.draw_winner(&lottery_id);
+    client.claim_prize(&user1, &lottery_id);
+
+    // Approvals are bound to the recipient
+    let action = ApprovalAction::Withdraw(treasury.clone());
+    let hash = client.propose_action(&approvers[0], &lottery_id, &action);
+    client.approve_action(&approvers[1], &lottery_id, &hash);
+
+    assert_eq!(client.withdraw_pot(&lottery_id, &treasury), 300);
+    assert_eq!(token_client.balance(&treasury), 300);
+    assert_eq!(client.get_lottery(&lottery_id).pot, 0);
+}
+
+#[test]
+#[should_panic(expected = "Approval required")]
+fn test_withdraw_pot_wrong_recipient() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let treasury = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.buy_ticket(&user1, &lottery_id, &3);
+    client.draw_winner(&lottery_id);
+    client.claim_prize(&user1, &lottery_id);
+
+    let approvers = setup_approvals(&env, &client, lottery_id);
+    let hash = client.propose_action(
+        &approvers[0],
+        &lottery_id,
+        &ApprovalAction::Withdraw(treasury),
+    );
+    client.approve_action(&approvers[1], &lottery_id, &hash);
+
+    client.withdraw_pot(&lottery_id, &Address::generate(&env));
+}
+
+/// Queue `action` and execute it once the timelock delay has passed
+fn run_timelocked(env: &Env, client: &NFTLotteryContractClient, action: TimelockAction) {
+    let hash = client.queue_action(&action);
+    env.ledger()
+        .with_mut(|li| li.timestamp += DEFAULT_TIMELOCK_DELAY);
+    client.execute_action(&hash);
+}
+
+/// Set the global fee through the timelock
+fn set_fee(env: &Env, client: &NFTLotteryContractClient, fee_bps: u32, recipient: &Address) {
+    run_timelocked(
+        env,
+        client,
+        TimelockAction::SetFee(fee_bps, recipient.clone()),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Timelock not expired")]
+fn test_timelock_too_early() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let fee_recipient = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let hash = client.queue_action(&TimelockAction::SetFee(250, fee_recipient));
+    env.ledger()
+        .with_mut(|li| li.timestamp += DEFAULT_TIMELOCK_DELAY - 1);
+    client.execute_action(&hash);
+}
+
+#[test]
+fn test_timelock_set_admin() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let new_admin = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let hash = client.queue_action(&TimelockAction::SetAdmin(new_admin.clone()));
+    assert_eq!(client.get_admin(), admin);
+    assert!(client.get_queued_action(&hash).is_some());
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += DEFAULT_TIMELOCK_DELAY);
+    client.execute_action(&hash);
+
+    assert_eq!(client.get_admin(), new_admin);
+    assert!(client.get_queued_action(&hash).is_none());
+}
+
+#[test]
+fn test_timelock_payment_token_and_delay() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let (new_token, _, _) = create_token_contract(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    // Shorten the delay, itself subject to the current delay
+    let hash = client.queue_action(&TimelockAction::SetDelay(60));
+    env.ledger()
+        .with_mut(|li| li.timestamp += DEFAULT_TIMELOCK_DELAY);
+    client.execute_action(&hash);
+
+    let hash = client.queue_action(&TimelockAction::SetPaymentToken(new_token.clone()));
+    env.ledger().with_mut(|li| li.timestamp += 60);
+    client.execute_action(&hash);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    assert_eq!(client.get_lottery(&lottery_id).payment_token, new_token);
+}
+
+#[test]
+#[should_panic(expected = "Action not queued")]
+fn test_timelock_cancel() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let fee_recipient = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let hash = client.queue_action(&TimelockAction::SetFee(250, fee_recipient));
+    client.cancel_action(&hash);
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += DEFAULT_TIMELOCK_DELAY);
+    client.execute_action(&hash);
+}
+
+#[test]
+#[should_panic(expected = "Cannot transfer to self")]
+fn test_transfer_ticket_to_self() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.buy_ticket(&user1, &lottery_id, &2);
+
+    client.transfer_ticket(&user1, &user1, &lottery_id, &1);
+}
+
+#[test]
+#[should_panic(expected = "Approval required")]
+fn test_replace_approvers_without_approval() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    setup_approvals(&env, &client, lottery_id);
+
+    // The admin alone can't swap in a 1-of-1 approver set
+    client.set_approvers(&soroban_sdk::Vec::from_array(&env, [admin.clone()]), &1);
+}
+
+#[test]
+fn test_replace_approvers_with_approval() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let new_approver = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    let approvers = setup_approvals(&env, &client, lottery_id);
+
+    let new_approvers = soroban_sdk::Vec::from_array(&env, [new_approver.clone()]);
+    let action = ApprovalAction::SetApprovers(new_approvers.clone(), 1);
+    let hash = client.propose_action(&approvers[0], &APPROVERS_PROPOSAL_ID, &action);
+    client.approve_action(&approvers[1], &APPROVERS_PROPOSAL_ID, &hash);
+    client.set_approvers(&new_approvers, &1);
+
+    // The new set is in charge from now on
+    let hash = client.propose_action(&new_approver, &lottery_id, &ApprovalAction::Draw);
+    assert!(client.get_proposal(&lottery_id, &hash).is_some());
+}
+
+#[test]
+#[should_panic(expected = "Lottery in progress")]
+fn test_disable_approval_while_open() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    let approvers = setup_approvals(&env, &client, lottery_id);
+
+    let hash = client.propose_action(&approvers[0], &lottery_id, &ApprovalAction::DisableApproval);
+    client.approve_action(&approvers[1], &lottery_id, &hash);
+    client.set_requires_approval(&lottery_id, &false);
+}
+
+#[test]
+#[should_panic(expected = "Timelock not expired")]
+fn test_lottery_fee_timelocked() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    let hash = client.queue_action(&TimelockAction::SetLotteryFee(
+        lottery_id,
+        Some(MAX_FEE_BPS),
+    ));
+    client.execute_action(&hash);
+}
+
+#[test]
+fn test_consolation_after_mega_winner_claims() {
+    let (env, admin, user1, token_address, token_client, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let user3 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    token_admin.mint(&user3, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.set_mega(&lottery_id, &1);
+    client.set_consolation(&lottery_id, &Consolation::Rebate(20));
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+    client.buy_ticket(&user3, &lottery_id, &1);
+
+    // Rebates for the two non-winners are set aside at draw time
+    let winner = client.draw_winner(&lottery_id);
+    assert_eq!(client.get_lottery(&lottery_id).pot, 300 - 40);
+
+    client.claim_prize(&winner, &lottery_id);
+    assert_eq!(token_client.balance(&winner), 100000 - 100 + 260);
+
+    for participant in [user1, user2, user3] {
+        if participant == winner {
+            continue;
+        }
+        client.claim_consolation(&participant, &lottery_id);
+        assert_eq!(token_client.balance(&participant), 100000 - 100 + 20);
+    }
+}
+
+#[test]
+fn test_leaderboard_bounded() {
+    let (env, admin, _, token_address, _, token_admin) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &1,
+        &100,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+
+    let mut top = None;
+    for i in 0..LEADERBOARD_SIZE + 5 {
+        let user = Address::generate(&env);
+        token_admin.mint(&user, &100);
+        client.buy_ticket(&user, &lottery_id, &if i == 7 { 3 } else { 1 });
+        if i == 7 {
+            top = Some(user);
+        }
+    }
+
+    let board = client.get_leaderboard(&100);
+    assert_eq!(board.len(), LEADERBOARD_SIZE);
+    assert_eq!(board.get(0).unwrap().0, top.unwrap());
+
+    assert_eq!(client.get_stats().unique_participants, LEADERBOARD_SIZE + 5);
+    let page = client.get_participants(&LEADERBOARD_SIZE, &10);
+    assert_eq!(page.len(), 5);
+}
+
+#[test]
+fn test_series_opens_next_round_on_cancel() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let series_id = client.create_series(
+        &100,
+        &20,
+        &0,
+        &String::from_str(&env, "Weekly Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &2,
+        &None,
+    );
+    let first_id = client.get_series(&series_id).current_lottery_id;
+    client.buy_ticket(&user1, &first_id, &1);
+
+    client.cancel_lottery(&first_id);
+
+    let series = client.get_series(&series_id);
+    assert_eq!(series.current_round, 2);
+    assert_eq!(
+        client.get_lottery(&series.current_lottery_id).status,
+        LotteryStatus::Open
+    );
+}
+
+#[test]
+fn test_advance_series_skips_unsold_round() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let series_id = client.create_series(
+        &100,
+        &20,
+        &0,
+        &String::from_str(&env, "Weekly Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &2,
+        &None,
+    );
+    let first_id = client.get_series(&series_id).current_lottery_id;
+
+    // Nobody bought in, so the round can't be drawn
+    let next_id = client.advance_series(&series_id);
+
+    assert_ne!(next_id, first_id);
+    assert_eq!(
+        client.get_lottery(&first_id).status,
+        LotteryStatus::Cancelled
+    );
+    assert_eq!(client.get_series(&series_id).current_round, 2);
+}
+
+#[test]
+#[should_panic(expected = "Round in progress")]
+fn test_advance_series_with_sales() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let series_id = client.create_series(
+        &100,
+        &20,
+        &0,
+        &String::from_str(&env, "Weekly Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &2,
+        &None,
+    );
+    let first_id = client.get_series(&series_id).current_lottery_id;
+    client.buy_ticket(&user1, &first_id, &1);
+
+    client.advance_series(&series_id);
+}
+
+#[test]
+#[should_panic(expected = "Can only increase max tickets")]
+fn test_update_max_tickets_sold_out_same_supply() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = client.create_lottery(
+        &100,
+        &2,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &None,
+    );
+    client.buy_ticket(&user1, &lottery_id, &2);
+
+    client.update_max_tickets(&lottery_id, &2);
+}
+
+#[test]
+#[should_panic(expected = "Invalid max tickets")]
+fn test_update_max_tickets_zero() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.update_max_tickets(&lottery_id, &0);
+}
+
+#[test]
+fn test_referral_rewards_refunded_on_cancel() {
+    let (env, admin, user1, token_address, token_client, _) = setup_test_env();
+    let referrer = Address::generate(&env);
+    let fee_recipient = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    set_fee(&env, &client, 250, &fee_recipient);
+    run_timelocked(&env, &client, TimelockAction::SetReferralBps(500));
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+
+    // 400 purchase: 10 fee, 20 referral reward held back, 370 to the pot
+    client.buy_ticket_with_referrer(&user1, &lottery_id, &4, &referrer);
+    client.cancel_lottery(&lottery_id);
+
+    // The buyer gets everything but the platform fee back
+    assert_eq!(client.claim_refund(&user1, &lottery_id), 390);
+    assert_eq!(token_client.balance(&user1), 100000 - 10);
+    assert_eq!(client.get_referral_rewards(&referrer, &token_address), 0);
+}
+
+#[test]
+#[should_panic]
+fn test_claim_free_ticket_other_deployment() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let other_contract = env.register_contract(None, NFTLotteryContract);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let signer = SigningKey::from_bytes(&[7u8; 32]);
+    client.set_voucher_signer(&BytesN::from_array(
+        &env,
+        &signer.verifying_key().to_bytes(),
+    ));
+
+    let lottery_id = client.create_lottery(
+        &0,
+        &100,
+        &String::from_str(&env, "Giveaway NFT"),
+        &String::from_str(&env, "https://example.com/giveaway.png"),
+        &1,
+        &None,
+    );
+
+    // Voucher issued for another deployment sharing the same signer
+    let signature = sign_voucher(&env, &other_contract, &signer, lottery_id, &user1, 1, 1);
+    client.claim_free_ticket(&user1, &lottery_id, &1, &1, &signature);
+}
+
+#[test]
+#[should_panic(expected = "Invalid payment token")]
+fn test_create_lottery_invalid_token() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    client.create_lottery(
+        &100,
+        &50,
+        &String::from_str(&env, "Dragon NFT"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &4,
+        &Some(Address::generate(&env)),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invalid payment token")]
+fn test_create_series_invalid_token() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    client.create_series(
+        &100,
+        &20,
+        &0,
+        &String::from_str(&env, "Weekly Dragon"),
+        &String::from_str(&env, "https://example.com/dragon.png"),
+        &2,
+        &Some(Address::generate(&env)),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invalid ticket count")]
+fn test_buy_zero_tickets() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.set_consolation(&lottery_id, &Consolation::Rebate(20));
+
+    // A free zero-ticket buy must not count as a holder and inflate the reserve
+    client.buy_ticket(&user1, &lottery_id, &0);
+}
+
+#[test]
+fn test_release_consolation_reserve() {
+    let (env, admin, user1, token_address, _, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let user3 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    token_admin.mint(&user3, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.set_consolation(&lottery_id, &Consolation::Rebate(20));
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+    client.buy_ticket(&user3, &lottery_id, &1);
+
+    let winner = client.draw_winner(&lottery_id);
+    let loser = if winner == user1 { user2 } else { user1 };
+    client.claim_consolation(&loser, &lottery_id);
+
+    // The other non-winner never claims; their rebate goes back to the pot
+    env.ledger()
+        .with_mut(|li| li.timestamp += DEFAULT_CLAIM_PERIOD + 1);
+    assert_eq!(client.release_consolation_reserve(&lottery_id), 20);
+    assert_eq!(client.get_lottery(&lottery_id).pot, 300 - 20);
+}
+
+#[test]
+#[should_panic(expected = "Delay too long")]
+fn test_timelock_delay_too_long() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    client.queue_action(&TimelockAction::SetDelay(MAX_TIMELOCK_DELAY + 1));
+}
+
+#[test]
+#[should_panic(expected = "Claim period too long")]
+fn test_claim_period_too_long() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    client.queue_action(&TimelockAction::SetClaimPeriod(u64::MAX));
+}
+
+#[test]
+fn test_timelock_max_delay_still_queues() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+    run_timelocked(&env, &client, TimelockAction::SetDelay(MAX_TIMELOCK_DELAY));
+
+    // A smaller delay can still be queued under the longest one
+    let hash = client.queue_action(&TimelockAction::SetDelay(60));
+    assert_eq!(
+        client.get_queued_action(&hash).unwrap().eta,
+        env.ledger().timestamp() + MAX_TIMELOCK_DELAY
+    );
+}
+
+#[test]
+#[should_panic(expected = "Timelock not expired")]
+fn test_timelock_upgrade_too_early() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let hash = client.queue_action(&TimelockAction::Upgrade(BytesN::from_array(&env, &[1; 32])));
+    client.execute_action(&hash);
+}
+
+#[test]
+#[should_panic(expected = "Error(Storage, MissingValue)")]
+fn test_timelock_upgrade_unknown_wasm() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    // Past the delay the upgrade reaches the deployer, which rejects a
+    // wasm hash that was never uploaded
+    run_timelocked(
+        &env,
+        &client,
+        TimelockAction::Upgrade(BytesN::from_array(&env, &[1; 32])),
+    );
+}
+
+#[test]
+fn test_zero_ticket_buy_not_counted() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    assert!(client.try_buy_ticket(&user1, &lottery_id, &0).is_err());
+    assert!(client.try_buy_ticket(&user2, &lottery_id, &0).is_err());
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    let stats = client.get_user_stats(&user1);
+    assert_eq!(stats.lotteries_entered, 1);
+    assert_eq!(stats.tickets_bought, 1);
+    assert_eq!(client.get_user_stats(&user2).lotteries_entered, 0);
+    assert_eq!(client.get_stats().unique_participants, 1);
+}
+
+#[test]
+fn test_consolation_after_redraw() {
+    let (env, admin, user1, token_address, token_client, token_admin) = setup_test_env();
+    let user2 = Address::generate(&env);
+    let user3 = Address::generate(&env);
+    token_admin.mint(&user2, &100000);
+    token_admin.mint(&user3, &100000);
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.set_consolation(&lottery_id, &Consolation::Rebate(20));
+
+    client.buy_ticket(&user1, &lottery_id, &1);
+    client.buy_ticket(&user2, &lottery_id, &1);
+    client.buy_ticket(&user3, &lottery_id, &1);
+
+    let first_winner = client.draw_winner(&lottery_id);
+    let mut others = std::vec::Vec::new();
+    for participant in [user1, user2, user3] {
+        if participant != first_winner {
+            others.push(participant);
+        }
+    }
+    client.claim_consolation(&others[0], &lottery_id);
+
+    // The consolation claimer is skipped, so the only candidate left wins
+    env.ledger()
+        .with_mut(|li| li.timestamp += DEFAULT_CLAIM_PERIOD + 1);
+    let new_winner = client.redraw(&lottery_id);
+    assert_eq!(new_winner, others[1]);
+
+    // The replaced winner can't collect a consolation prize either
+    assert!(client
+        .try_claim_consolation(&first_winner, &lottery_id)
+        .is_err());
+    assert!(client
+        .try_claim_consolation(&new_winner, &lottery_id)
+        .is_err());
+    assert_eq!(token_client.balance(&first_winner), 100000 - 100);
+}
+
+#[test]
+#[should_panic(expected = "Can only increase max tickets")]
+fn test_update_max_tickets_open_same_supply() {
+    let (env, admin, user1, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    client.buy_ticket(&user1, &lottery_id, &1);
+
+    let max_tickets = client.get_lottery(&lottery_id).max_tickets;
+    client.update_max_tickets(&lottery_id, &max_tickets);
+}
+
+#[test]
+fn test_update_ticket_price_invalid() {
+    let (env, admin, _, token_address, _, _) = setup_test_env();
+    let contract_id = env.register_contract(None, NFTLotteryContract);
+    let client = NFTLotteryContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &token_address);
+
+    let lottery_id = create_lotteries(&env, &client, 1).get(0).unwrap();
+    assert!(client.try_update_ticket_price(&lottery_id, &0).is_err());
+    assert!(client.try_update_ticket_price(&lottery_id, &-100).is_err());
+    assert_eq!(client.get_lottery(&lottery_id).ticket_price, 100);
+}
@@ -5,6 +5,7 @@ import {
   getLottery,
   getUserTickets,
   prepareTransaction,
+  isLotteryActive,
   type Lottery,
   CONTRACT_ID,
   ADMIN_ADDRESS,
@@ -107,14 +108,15 @@ function App() {
         return;
       }
 
-      const adminAddr = Address.fromString(wallet.address);
+      // The contract checks the stored admin's signature itself
       const args = [
-        nativeToScVal(adminAddr, { type: "address" }),
         nativeToScVal(ticketPrice, { type: "i128" }),
         nativeToScVal(maxTickets, { type: "u32" }),
         nativeToScVal(createForm.nft_name, { type: "string" }),
         nativeToScVal(createForm.nft_image, { type: "string" }),
         nativeToScVal(rarity, { type: "u32" }),
+        // payment_token: None uses the contract-wide token
+        nativeToScVal(null),
       ];
 
       const txXdr = await prepareTransaction(
@@ -222,11 +224,7 @@ function App() {
       setProcessing(true);
       setError(null);
 
-      const adminAddr = Address.fromString(wallet.address);
-      const args = [
-        nativeToScVal(adminAddr, { type: "address" }),
-        nativeToScVal(lotteryId, { type: "u64" }),
-      ];
+      const args = [nativeToScVal(lotteryId, { type: "u64" })];
 
       const txXdr = await prepareTransaction(
         "draw_winner",
@@ -537,7 +535,7 @@ function App() {
                       </span>
                     </div>
                     <div className="lottery-status">
-                      {lottery.is_active ? (
+                      {isLotteryActive(lottery) ? (
                         <span className="status-active">🟢 Active</span>
                       ) : (
                         <span className="status-ended">🔴 Ended</span>
@@ -615,7 +613,7 @@ function App() {
                         )}
                       </div>
                     )}
-                    {!lottery.is_active && !lottery.winner && (
+                    {!isLotteryActive(lottery) && !lottery.winner && (
                       <div
                         className="info-row"
                         style={{
@@ -632,7 +630,7 @@ function App() {
                   <div className="lottery-actions">
                     {wallet.isConnected && wallet.address && (
                       <>
-                        {lottery.is_active ? (
+                        {isLotteryActive(lottery) ? (
                           <>
                             {lottery.tickets_sold < lottery.max_tickets && (
                               <button
@@ -27,12 +27,21 @@ export const ADMIN_ADDRESS =
   import.meta.env.VITE_ADMIN_ADDRESS ||
   "GB7UYMD3K7CLHC374ZRZDAR5ORC55P7BIFTSZZT426GS6OQHVFP5G3XG";
 
+export type LotteryStatus =
+  | "Open"
+  | "SoldOut"
+  | "Drawn"
+  | "Claimed"
+  | "Reclaimed"
+  | "Cancelled"
+  | "Failed";
+
 export interface Lottery {
   id: string;
   ticket_price: string;
   max_tickets: number;
   tickets_sold: number;
-  is_active: boolean;
+  status: LotteryStatus;
   winner: string | null;
   nft_prize: {
     name: string;
@@ -41,6 +50,10 @@ export interface Lottery {
   };
 }
 
+// Tickets can still be sold or the winner drawn
+export const isLotteryActive = (lottery: Lottery): boolean =>
+  lottery.status === "Open" || lottery.status === "SoldOut";
+
 export interface NFTMetadata {
   name: string;
   image_url: string;
@@ -176,7 +189,10 @@ export const getLottery = async (
           ticket_price: lottery.ticket_price.toString(),
           max_tickets: Number(lottery.max_tickets),
           tickets_sold: Number(lottery.tickets_sold),
-          is_active: lottery.is_active,
+          // Unit enum variants decode as a one-element array, e.g. ["Open"]
+          status: (Array.isArray(lottery.status)
+            ? lottery.status[0]
+            : lottery.status) as LotteryStatus,
           winner: winnerAddress,
           nft_prize: {
             name: lottery.nft_prize.name.toString(),